@@ -0,0 +1,236 @@
+//! Acknowledgement of active errors over HTTP, which stops their escalation.
+//!
+//! `POST /ack/<resource>` acknowledges active error of resource, `GET /ack` lists active errors
+//! with their state. Acknowledgement applies only to error, which is active at the moment, or
+//! to the one, which started at `since` query parameter (RFC 3339), if it is set.
+
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use futures::{sync::oneshot, Future};
+use hyper::{
+    header::AUTHORIZATION, service::service_fn_ok, Body, Method, Request, Response, Server,
+    StatusCode,
+};
+use percent_encoding::percent_decode;
+
+use log::{error, info};
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::BoxedFuture;
+
+#[derive(Debug, Fail)]
+pub(crate) enum AckError {
+    #[fail(display = "Failed to bind ack server to {}: {}", addr, err)]
+    BindError { addr: SocketAddr, err: hyper::Error },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct AckConfig {
+    pub listen: SocketAddr,
+    /// Bearer token, required by server, if set.
+    pub token: Option<String>,
+}
+
+/// Active error of resource.
+#[derive(Clone, Copy)]
+struct ActiveError {
+    /// When error was first observed, which identifies it among errors of resource.
+    since: DateTime<Utc>,
+    acked: bool,
+}
+
+/// Active errors of resources with escalation, and whether they are acknowledged.
+#[derive(Default)]
+pub(crate) struct Acks {
+    active: Mutex<BTreeMap<String, ActiveError>>,
+}
+
+impl Acks {
+    /// Register active error of resource, which started at `since`, if it isn't registered
+    /// yet. Acknowledgement of previous error doesn't apply to it.
+    pub(crate) fn raise(&self, resource: &str, since: DateTime<Utc>) {
+        let mut active = self.active.lock().unwrap();
+        if active.get(resource).map(|x| x.since) != Some(since) {
+            active.insert(
+                resource.into(),
+                ActiveError {
+                    since,
+                    acked: false,
+                },
+            );
+        }
+    }
+
+    /// Forget resolved error of resource with its acknowledgement.
+    pub(crate) fn clear(&self, resource: &str) {
+        self.active.lock().unwrap().remove(resource);
+    }
+
+    /// Whether error of resource, which started at `since`, is acknowledged.
+    pub(crate) fn is_acked(&self, resource: &str, since: DateTime<Utc>) -> bool {
+        self.active
+            .lock()
+            .unwrap()
+            .get(resource)
+            .map(|x| x.since == since && x.acked)
+            .unwrap_or(false)
+    }
+
+    /// Acknowledge active error of resource, which started at `since`, if it is set. Returns
+    /// `false`, if there is no such error.
+    pub(crate) fn ack(&self, resource: &str, since: Option<DateTime<Utc>>) -> bool {
+        match self.active.lock().unwrap().get_mut(resource) {
+            Some(error) if since.map(|x| x == error.since).unwrap_or(true) => {
+                error.acked = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Active errors as JSON object by resource.
+    fn to_json(&self) -> serde_json::Value {
+        let active = self.active.lock().unwrap();
+        let errors = active
+            .iter()
+            .map(|(resource, error)| {
+                let error = serde_json::json!({
+                    "since": error.since.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                    "acked": error.acked,
+                });
+                (resource.clone(), error)
+            })
+            .collect::<serde_json::Map<_, _>>();
+        errors.into()
+    }
+}
+
+/// Bind ack server, which runs until `stop` resolves.
+pub(crate) fn serve(
+    config: &AckConfig,
+    acks: Arc<Acks>,
+    stop: oneshot::Receiver<()>,
+) -> Result<BoxedFuture<(), ()>, AckError> {
+    let builder = Server::try_bind(&config.listen).map_err(|e| AckError::BindError {
+        addr: config.listen,
+        err: e,
+    })?;
+    let token = config.token.clone();
+    let server = builder
+        .serve(move || {
+            let acks = acks.clone();
+            let token = token.clone();
+            service_fn_ok(move |req| handle(&acks, token.as_deref(), &req))
+        })
+        .with_graceful_shutdown(stop.then(|_| Ok::<_, ()>(())))
+        .map_err(|e| error!("Ack server error: {}", e));
+    info!("Ack server listens on {}", config.listen);
+    Ok(Box::new(server))
+}
+
+/// Compare secrets in time, which doesn't depend on position of first mismatch.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Start of error from `since` query parameter.
+fn since_param(req: &Request<Body>) -> Result<Option<DateTime<Utc>>, ()> {
+    let value = req
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|x| x.strip_prefix("since="));
+    match value {
+        Some(value) => percent_decode(value.as_bytes())
+            .decode_utf8()
+            .ok()
+            .and_then(|x| DateTime::parse_from_rfc3339(&x).ok())
+            .map(|x| Some(x.with_timezone(&Utc)))
+            .ok_or(()),
+        None => Ok(None),
+    }
+}
+
+fn handle(acks: &Acks, token: Option<&str>, req: &Request<Body>) -> Response<Body> {
+    let authorized = token
+        .map(|token| {
+            req.headers()
+                .get(AUTHORIZATION)
+                .map(|x| constant_time_eq(x.as_bytes(), format!("Bearer {}", token).as_bytes()))
+                .unwrap_or(false)
+        })
+        .unwrap_or(true);
+    let path = req.uri().path();
+    let (status, body): (StatusCode, String) = if !authorized {
+        (StatusCode::UNAUTHORIZED, "Unauthorized".into())
+    } else if path == "/ack" && req.method() == Method::GET {
+        (StatusCode::OK, acks.to_json().to_string())
+    } else if let Some(resource) = path.strip_prefix("/ack/") {
+        let since = since_param(req);
+        match percent_decode(resource.as_bytes()).decode_utf8() {
+            _ if req.method() != Method::POST => {
+                (StatusCode::METHOD_NOT_ALLOWED, "Method not allowed".into())
+            }
+            _ if since.is_err() => (StatusCode::BAD_REQUEST, "Invalid 'since'".into()),
+            Ok(ref resource) if acks.ack(resource, since.unwrap()) => {
+                info!("Error of {} is acknowledged", resource);
+                (StatusCode::OK, "Acknowledged".into())
+            }
+            _ => (
+                StatusCode::NOT_FOUND,
+                "No active error to acknowledge".into(),
+            ),
+        }
+    } else {
+        (StatusCode::NOT_FOUND, "Not found".into())
+    };
+    Response::builder()
+        .status(status)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_time_eq_compares_bytes() {
+        assert!(constant_time_eq(b"Bearer token", b"Bearer token"));
+        assert!(!constant_time_eq(b"Bearer token", b"Bearer tokeN"));
+        assert!(!constant_time_eq(b"Bearer token", b"Bearer token2"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
+    #[test]
+    fn ack_applies_to_current_error_only() {
+        let acks = Acks::default();
+        let first = Utc::now();
+        let second = first + chrono::Duration::seconds(1);
+        assert!(!acks.ack("db", None));
+        acks.raise("db", first);
+        assert!(!acks.ack("db", Some(second)));
+        assert!(acks.ack("db", Some(first)));
+        assert!(acks.is_acked("db", first));
+        // The same error stays acknowledged.
+        acks.raise("db", first);
+        assert!(acks.is_acked("db", first));
+        // The next error isn't.
+        acks.raise("db", second);
+        assert!(!acks.is_acked("db", second));
+        assert!(acks.ack("db", None));
+        assert!(acks.is_acked("db", second));
+        acks.clear("db");
+        assert!(!acks.is_acked("db", second));
+    }
+}
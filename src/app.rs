@@ -2,19 +2,23 @@ use std::collections::BTreeMap;
 use std::env;
 use std::error::Error;
 use std::fs::File;
+use std::sync::Arc;
 
 use serde::Deserialize;
 
 use failure::Fail;
 
 use futures::{
-    future::{ok, Future},
+    future::{empty, join_all, lazy, ok, Future},
     stream::{iter_ok, Stream},
+    sync::oneshot,
 };
+use tokio_signal::unix::{Signal, SIGTERM};
 
 use crate::{
+    ack::{self, Acks},
     notifier::{self, Notifier},
-    sentinel, BoxedStream,
+    sentinel, BoxedFuture, BoxedStream,
 };
 
 #[derive(Debug, Fail)]
@@ -26,6 +30,9 @@ pub(crate) enum SentinelAppError {
 
     #[fail(display = "Unknown sentinel type '{}'", ty)]
     UnknownSentinelType { ty: String },
+
+    #[fail(display = "Unknown escalation policy '{}'", name)]
+    UnknownEscalationPolicy { name: String },
 }
 
 pub(crate) fn load_env_config() -> GlobalConfig {
@@ -44,37 +51,88 @@ pub(crate) fn load_env_config() -> GlobalConfig {
 pub(crate) struct GlobalConfig {
     resources: Vec<sentinel::YamlConfig>,
     notifiers: Vec<notifier::YamlConfig>,
+    #[serde(default)]
+    escalations: Vec<EscalationPolicy>,
+    /// Server, which acknowledges errors to stop their escalation.
+    ack: Option<ack::AckConfig>,
+    /// Limit of messages, shared by all notifiers.
+    rate_limit: Option<notifier::RateLimitConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct EscalationPolicy {
+    name: String,
+    tiers: Vec<EscalationTier>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct EscalationTier {
+    /// How long error stays active before tier is notified, in milliseconds.
+    after: u64,
+    notifiers: Vec<String>,
 }
 
 pub(crate) struct SentinelApp {
     notifiers: BTreeMap<String, Box<dyn Notifier>>,
     resources_streams: Vec<BoxedStream<(), Box<dyn Error + Send>>>,
+    ack_server: Option<BoxedFuture<(), ()>>,
+    /// Stops ack server on shutdown.
+    stop_ack: Option<oneshot::Sender<()>>,
 }
 
 impl SentinelApp {
     pub(crate) fn new(config: GlobalConfig) -> Result<Self, Box<dyn Fail>> {
-        let notifiers = Self::notifiers_from_configs(config.notifiers)?;
+        let global_limit = config
+            .rate_limit
+            .as_ref()
+            .map(|x| Arc::new(notifier::TokenBucket::new(x)));
+        let notifiers = Self::notifiers_from_configs(config.notifiers, global_limit)?;
+        let senders = |names: &[String]| {
+            names
+                .iter()
+                .map(|notifier_name| {
+                    notifiers
+                        .get(notifier_name)
+                        .map(|x| x.sender())
+                        .ok_or_else(|| SentinelAppError::UnknownNotifierName {
+                            name: notifier_name.clone(),
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let acks = Arc::new(Acks::default());
+        let escalations = &config.escalations;
         let sentinel_configs = config
             .resources
             .into_iter()
             .map(|x| {
-                let senders = x
-                    .notifiers
-                    .iter()
-                    .map(|notifier_name| {
-                        notifiers
-                            .get(notifier_name)
-                            .map(|x| x.sender())
-                            .ok_or_else(|| SentinelAppError::UnknownNotifierName {
-                                name: notifier_name.clone(),
+                let escalation = match x.escalation {
+                    Some(ref name) => escalations
+                        .iter()
+                        .find(|p| &p.name == name)
+                        .ok_or_else(|| SentinelAppError::UnknownEscalationPolicy {
+                            name: name.clone(),
+                        })?
+                        .tiers
+                        .iter()
+                        .map(|tier| {
+                            Ok(sentinel::Tier {
+                                after: chrono::Duration::milliseconds(tier.after as i64),
+                                notifiers: senders(&tier.notifiers)?,
                             })
-                    })
-                    .collect::<Result<Vec<_>, _>>()?;
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                    None => Vec::new(),
+                };
                 Ok(sentinel::Config {
                     interval: x.interval,
                     name: x.name,
                     type_: x.type_,
-                    notifiers: senders,
+                    notifiers: senders(&x.notifiers)?,
+                    info: x.info,
+                    reminder: x.reminder,
+                    escalation,
+                    acks: acks.clone(),
                     config: x.config,
                 })
             })
@@ -84,43 +142,117 @@ impl SentinelApp {
             .into_iter()
             .map(|x| match x.type_.as_ref() {
                 "http" => sentinel::http::HttpSentinel::create_sentinel_stream(x),
+                "grpc" => sentinel::grpc::GrpcSentinel::create_sentinel_stream(x),
+                "websocket" => sentinel::websocket::WebSocketSentinel::create_sentinel_stream(x),
+                "ntp" => sentinel::ntp::NtpSentinel::create_sentinel_stream(x),
+                "mail_roundtrip" => {
+                    sentinel::mail_roundtrip::MailRoundtripSentinel::create_sentinel_stream(x)
+                }
                 ty => Err(
                     Box::new(SentinelAppError::UnknownSentinelType { ty: ty.into() })
                         as Box<dyn Fail>,
                 )?,
             })
             .collect::<Result<Vec<_>, _>>()?;
+        let (stop_ack, ack_server) = match config.ack {
+            Some(ref ack) => {
+                let (stop, stopped) = oneshot::channel();
+                let server =
+                    ack::serve(ack, acks, stopped).map_err(|e| Box::new(e) as Box<dyn Fail>)?;
+                (Some(stop), Some(server))
+            }
+            None => (None, None),
+        };
         Ok(Self {
             notifiers,
             resources_streams,
+            ack_server,
+            stop_ack,
         })
     }
 
     fn notifiers_from_configs(
         configs: Vec<notifier::YamlConfig>,
+        global_limit: Option<Arc<notifier::TokenBucket>>,
     ) -> Result<BTreeMap<String, Box<dyn Notifier>>, Box<dyn Fail>> {
-        configs
+        let notifiers = configs
             .into_iter()
-            .map(|config| {
-                let name = config.name;
+            .map(|mut config| {
+                let value = std::mem::take(&mut config.config);
                 let notifier = match config.type_.as_ref() {
                     // Add here new type of notifiers.
-                    "smtp" => notifier::smtp::SmtpNotifier::from_config(config.config)?,
+                    "smtp" => notifier::smtp::SmtpNotifier::from_config(value)?,
+                    "webhook" => notifier::webhook::WebhookNotifier::from_config(value)?,
+                    "slack" => notifier::slack::SlackNotifier::from_config(value)?,
+                    "telegram" => notifier::telegram::TelegramNotifier::from_config(value)?,
+                    "pagerduty" => notifier::pagerduty::PagerDutyNotifier::from_config(value)?,
+                    "alertmanager" => {
+                        notifier::alertmanager::AlertmanagerNotifier::from_config(value)?
+                    }
+                    "exec" => notifier::exec::ExecNotifier::from_config(value)?,
+                    "syslog" => notifier::syslog::SyslogNotifier::from_config(value)?,
+                    "journald" => notifier::journald::JournaldNotifier::from_config(value)?,
+                    "file" => notifier::file::FileNotifier::from_config(value)?,
+                    "matrix" => notifier::matrix::MatrixNotifier::from_config(value)?,
+                    "teams" => notifier::teams::TeamsNotifier::from_config(value)?,
+                    "discord" => notifier::discord::DiscordNotifier::from_config(value)?,
                     ty => Err(
                         Box::new(SentinelAppError::UnknownNotifierType { ty: ty.into() })
                             as Box<dyn Fail>,
                     )?,
                 };
-                Ok((name, notifier))
+                Ok((notifier, config))
             })
-            .collect::<Result<Vec<_>, _>>()
-            .map(|v| v.into_iter().collect::<BTreeMap<_, _>>())
+            .collect::<Result<Vec<_>, Box<dyn Fail>>>()?;
+        // Fallbacks are plain notifiers, without their own fallbacks, batching and reminders.
+        let fallbacks = notifiers
+            .iter()
+            .map(|(_, config)| {
+                config
+                    .fallback
+                    .iter()
+                    .map(|name| {
+                        notifiers
+                            .iter()
+                            .find(|x| &x.1.name == name)
+                            .map(|x| (name.clone(), x.0.sender()))
+                            .ok_or_else(|| {
+                                Box::new(SentinelAppError::UnknownNotifierName {
+                                    name: name.clone(),
+                                }) as Box<dyn Fail>
+                            })
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(notifiers
+            .into_iter()
+            .zip(fallbacks)
+            .map(|((notifier, config), fallbacks)| {
+                let name = config.name;
+                // Digests fall back as a whole.
+                let notifier = notifier::FallbackNotifier::wrap(name.clone(), notifier, fallbacks);
+                // Digest counts as single message.
+                let notifier = notifier::RateLimitNotifier::wrap(
+                    notifier,
+                    config.rate_limit,
+                    global_limit.clone(),
+                );
+                let notifier = match config.batch {
+                    Some(batch) => notifier::BatchNotifier::wrap(notifier, batch),
+                    None => notifier,
+                };
+                // Reminders are batched too.
+                let notifier = notifier::ReminderNotifier::wrap(notifier, config.reminder);
+                (name, notifier)
+            })
+            .collect())
     }
 
     pub(crate) fn run(&mut self) {
         let num_of_resources = self.resources_streams.len();
         let streams = self.resources_streams.drain(..).collect::<Vec<_>>();
-        let task = iter_ok(streams)
+        let sentinels = iter_ok(streams)
             .map(|stream| {
                 stream
                     .for_each(|_| ok(()))
@@ -128,6 +260,51 @@ impl SentinelApp {
             })
             .buffer_unordered(num_of_resources)
             .for_each(|_| Ok(()));
+        let backgrounds = self
+            .notifiers
+            .values()
+            .filter_map(|x| x.background())
+            .collect::<Vec<_>>();
+        let shutdowns = self
+            .notifiers
+            .values()
+            .map(|x| x.shutdown())
+            .collect::<Vec<_>>();
+        let ack_server = self.ack_server.take();
+        let stop_ack = self.stop_ack.take();
+        let task = lazy(move || {
+            for background in backgrounds {
+                tokio::spawn(background);
+            }
+            if let Some(ack_server) = ack_server {
+                tokio::spawn(ack_server);
+            }
+            // Pending notifications are flushed, before runtime is stopped.
+            sentinels.select2(shutdown_signal()).then(move |_| {
+                log::info!("Shutting down");
+                if let Some(stop_ack) = stop_ack {
+                    let _ = stop_ack.send(());
+                }
+                join_all(shutdowns).map(|_| ())
+            })
+        });
         tokio::run(task);
     }
 }
+
+/// Resolves on SIGINT or SIGTERM.
+fn shutdown_signal() -> BoxedFuture<(), ()> {
+    let ctrl_c = tokio_signal::ctrl_c().flatten_stream();
+    let term = Signal::new(SIGTERM).flatten_stream().map(|_| ());
+    Box::new(
+        ctrl_c
+            .select(term)
+            .into_future()
+            .map(|_| ())
+            .or_else(|(e, _)| {
+                // Keep running without graceful shutdown.
+                log::error!("Failed to listen for signals: {}", e);
+                empty()
+            }),
+    )
+}
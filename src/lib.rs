@@ -1,5 +1,6 @@
 use tokio::prelude::*;
 
+mod ack;
 mod app;
 mod notifier;
 mod sentinel;
@@ -8,6 +9,15 @@ mod sentinel;
 pub(crate) type BoxedFuture<R, E> = Box<dyn Future<Item = R, Error = E> + Send>;
 pub(crate) type BoxedStream<R, E> = Box<dyn Stream<Item = R, Error = E> + Send>;
 
+/// Run blocking operation (DNS lookup, synchronous client, file IO) on runtime's thread pool.
+pub(crate) fn blocking<F, T>(f: F) -> impl Future<Item = T, Error = tokio_threadpool::BlockingError>
+where
+    F: FnOnce() -> T,
+{
+    let mut f = Some(f);
+    future::poll_fn(move || tokio_threadpool::blocking(|| (f.take().unwrap())()))
+}
+
 pub fn main() {
     env_logger::init();
 
@@ -0,0 +1,307 @@
+//! Batching of messages into digests, applicable to any notifier.
+//!
+//! First message is sent right away and opens window. Messages, which arrive within window, are
+//! sent as single digest, when window ends or `max_count` of them is collected.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::debug;
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{
+        wrapper::{Window, Wrapped, Wrapper},
+        Message, MessageKind, Notifier, NotifierSender, ResourceInfo,
+    },
+    BoxedFuture,
+};
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct BatchConfig {
+    /// How long messages are collected after the first one, in milliseconds.
+    #[serde(default = "default_window")]
+    window: u64,
+    /// Digest is sent before window ends, when this many messages are collected.
+    #[serde(default = "default_max_count")]
+    max_count: usize,
+}
+
+fn default_window() -> u64 {
+    30_000
+}
+
+fn default_max_count() -> usize {
+    50
+}
+
+/// Wrapper, which batches messages of inner notifier.
+pub(crate) type BatchNotifier = Wrapper<Batch>;
+
+impl BatchNotifier {
+    pub(crate) fn wrap(inner: Box<dyn Notifier>, config: BatchConfig) -> Box<dyn Notifier> {
+        let batch = Batch {
+            window: Duration::from_millis(config.window),
+            // Digest of nothing is meaningless.
+            max_count: config.max_count.max(1),
+            state: Mutex::new(BatchState {
+                sender: inner.sender(),
+                pending: Vec::new(),
+                window: Window::default(),
+            }),
+        };
+        Wrapper::boxed(inner, batch)
+    }
+}
+
+pub(crate) struct Batch {
+    window: Duration,
+    max_count: usize,
+    state: Mutex<BatchState>,
+}
+
+impl Wrapped for Batch {
+    fn sender(self: Arc<Self>) -> Box<dyn NotifierSender> {
+        Box::new(BatchSender { batch: self })
+    }
+
+    fn close(&self) -> BoxedFuture<(), Box<dyn Fail>> {
+        let mut state = self.state.lock().unwrap();
+        state.window.close();
+        state.flush()
+    }
+}
+
+struct BatchState {
+    sender: Box<dyn NotifierSender>,
+    pending: Vec<Message>,
+    window: Window,
+}
+
+impl BatchState {
+    /// Send collected messages as digest, or as is, if there is only one.
+    fn flush(&mut self) -> BoxedFuture<(), Box<dyn Fail>> {
+        let msg = match self.pending.len() {
+            0 => return Box::new(futures::future::ok(())),
+            1 => self.pending.pop().unwrap(),
+            n => {
+                debug!("Send digest of {} messages", n);
+                Message::combine(self.pending.drain(..).collect(), digest)
+            }
+        };
+        self.sender.send_message(msg)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct BatchSender {
+    batch: Arc<Batch>,
+}
+
+impl NotifierSender for BatchSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let mut state = self.batch.state.lock().unwrap();
+        if state.window.is_closed() {
+            return state.sender.send_message(msg);
+        }
+        if state.window.is_open() {
+            state.pending.push(msg);
+            return if state.pending.len() >= self.batch.max_count {
+                state.flush()
+            } else {
+                Box::new(futures::future::ok(()))
+            };
+        }
+        let batch = self.batch.clone();
+        state.window.open(self.batch.window, move || {
+            let mut state = batch.state.lock().unwrap();
+            state.window.ended();
+            state.flush()
+        });
+        state.sender.send_message(msg)
+    }
+}
+
+/// Single message, which lists messages grouped by state and resource, or the only message.
+fn digest(messages: &[Message]) -> Message {
+    if let [msg] = messages {
+        return msg.clone();
+    }
+    let mut groups = BTreeMap::new();
+    for msg in messages {
+        groups
+            .entry(state_order(msg.kind))
+            .or_insert_with(BTreeMap::new)
+            .entry(msg.resource_name.as_str())
+            .or_insert_with(Vec::new)
+            .push(msg.description.as_str());
+    }
+    // The most alarming state of digest.
+    let kind = STATES[*groups.keys().next().expect("digest of no messages")];
+    let counts = groups
+        .iter()
+        .map(|(state, resources)| {
+            let n: usize = resources.values().map(Vec::len).sum();
+            format!("{} {}", n, STATES[*state].as_str())
+        })
+        .collect::<Vec<_>>();
+    let title = format!(
+        "Digest of {} messages ({})",
+        messages.len(),
+        counts.join(", ")
+    );
+    let mut body = String::new();
+    for (state, resources) in &groups {
+        body.push_str(&format!("{}:\n", STATES[*state].label()));
+        for (resource, descriptions) in resources {
+            for description in descriptions {
+                body.push_str(&format!("  {}: {}\n", resource, description));
+            }
+        }
+    }
+    let resource_name = groups
+        .values()
+        .flat_map(|x| x.keys().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>()
+        .join(", ");
+    let since = messages.iter().map(|x| x.since).min().unwrap();
+    // Labels, shared by all messages, and the highest severity.
+    let mut info = ResourceInfo {
+        severity: messages.iter().map(|x| x.info.severity).max().unwrap(),
+        labels: messages[0].info.labels.clone(),
+        runbook: None,
+    };
+    info.labels.retain(|k, v| {
+        messages
+            .iter()
+            .all(|x| x.info.labels.get(k).map(|x| x == v).unwrap_or(false))
+    });
+    Message::new(
+        title,
+        body.trim_end().into(),
+        kind,
+        resource_name,
+        counts.join(", "),
+        since,
+    )
+    .with_context(Vec::new(), info)
+}
+
+/// States in order of digest.
+const STATES: [MessageKind; 4] = [
+    MessageKind::New,
+    MessageKind::Changed,
+    MessageKind::Reminder,
+    MessageKind::Resolved,
+];
+
+fn state_order(kind: MessageKind) -> usize {
+    STATES.iter().position(|x| *x == kind).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::notifier::Severity;
+
+    fn message(resource: &str, kind: MessageKind, severity: Severity) -> Message {
+        let mut labels = BTreeMap::new();
+        labels.insert("env".to_string(), "prod".to_string());
+        labels.insert("team".to_string(), resource.to_string());
+        Message::new(
+            format!("{} {}", kind.label(), resource),
+            String::new(),
+            kind,
+            resource.into(),
+            format!("{} is {}", resource, kind.as_str()),
+            Utc::now(),
+        )
+        .with_context(
+            Vec::new(),
+            ResourceInfo {
+                severity: Some(severity),
+                labels,
+                runbook: None,
+            },
+        )
+    }
+
+    fn batch() -> Vec<Message> {
+        vec![
+            message("db-1", MessageKind::New, Severity::Error),
+            message("web-2", MessageKind::Changed, Severity::Critical),
+            message("db-1", MessageKind::Resolved, Severity::Error),
+        ]
+    }
+
+    #[test]
+    fn digest_groups_by_state() {
+        let msg = digest(&batch());
+        assert_eq!(
+            msg.title,
+            "Digest of 3 messages (1 new, 1 changed, 1 resolved)"
+        );
+        assert_eq!(
+            msg.body,
+            "New:\n  db-1: db-1 is new\nChanged:\n  web-2: web-2 is changed\n\
+             Resolved:\n  db-1: db-1 is resolved"
+        );
+        assert_eq!(msg.kind, MessageKind::New);
+        assert_eq!(msg.info.severity, Some(Severity::Critical));
+        // Only labels, shared by all messages.
+        assert_eq!(msg.info.labels.len(), 1);
+        assert_eq!(msg.info.labels["env"], "prod");
+    }
+
+    #[test]
+    fn digest_of_one_message_is_message() {
+        let msg = digest(&batch()[..1]);
+        assert_eq!(msg.title, "New db-1");
+        assert_eq!(msg.kind, MessageKind::New);
+    }
+
+    #[test]
+    fn digest_splits_into_last_message_per_resource() {
+        let msg = Message::combine(batch(), digest);
+        let parts = msg.per_resource();
+        let parts = parts
+            .iter()
+            .map(|x| (x.resource_name.as_str(), x.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            parts,
+            vec![
+                ("web-2", MessageKind::Changed),
+                ("db-1", MessageKind::Resolved)
+            ]
+        );
+    }
+
+    #[test]
+    fn digest_is_filtered_by_part() {
+        let msg = Message::combine(batch(), digest);
+        let db = msg
+            .filter(&|m: &Message| m.resource_name.starts_with("db-"))
+            .unwrap();
+        assert_eq!(db.title, "Digest of 2 messages (1 new, 1 resolved)");
+        assert_eq!(db.resource_name, "db-1");
+        assert_eq!(db.per_resource().len(), 1);
+        let web = msg
+            .filter(&|m: &Message| m.resource_name.starts_with("web-"))
+            .unwrap();
+        assert_eq!(web.title, "Changed web-2");
+        assert!(msg
+            .filter(&|m: &Message| m.resource_name == "mail")
+            .is_none());
+    }
+}
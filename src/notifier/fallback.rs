@@ -0,0 +1,82 @@
+//! Fallback chains: message, which notifier failed to deliver, is sent through the next one.
+
+use std::sync::{Arc, Mutex};
+
+use futures::{future::err, Future};
+
+use log::warn;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{
+        wrapper::{Wrapped, Wrapper},
+        Message, Notifier, NotifierSender,
+    },
+    BoxedFuture,
+};
+
+/// Wrapper, which tries fallback notifiers in order, until one of them delivers message.
+pub(crate) type FallbackNotifier = Wrapper<Chain>;
+
+impl FallbackNotifier {
+    /// Fallbacks are named senders of other notifiers.
+    pub(crate) fn wrap(
+        name: String,
+        inner: Box<dyn Notifier>,
+        fallbacks: Vec<(String, Box<dyn NotifierSender>)>,
+    ) -> Box<dyn Notifier> {
+        if fallbacks.is_empty() {
+            return inner;
+        }
+        let mut senders = vec![(name, inner.sender())];
+        senders.extend(fallbacks);
+        let chain = Chain {
+            senders: Mutex::new(senders),
+        };
+        Wrapper::boxed(inner, chain)
+    }
+}
+
+pub(crate) struct Chain {
+    /// Named senders of inner notifier and its fallbacks, in order.
+    senders: Mutex<Vec<(String, Box<dyn NotifierSender>)>>,
+}
+
+impl Wrapped for Chain {
+    fn sender(self: Arc<Self>) -> Box<dyn NotifierSender> {
+        Box::new(FallbackSender { chain: self })
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct FallbackSender {
+    chain: Arc<Chain>,
+}
+
+/// Send message through notifier at `index` of chain, or the following ones, if it fails.
+fn send_from(chain: Arc<Chain>, index: usize, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+    let senders = chain.senders.lock().unwrap();
+    let (ref name, ref sender) = senders[index];
+    let next = senders
+        .get(index + 1)
+        .map(|(next, _)| (name.clone(), next.clone()));
+    let fut = sender.send_message(msg.clone());
+    drop(senders);
+    Box::new(fut.or_else(move |e| match next {
+        Some((name, next)) => {
+            warn!(
+                "Message about {} is not delivered by {}, fall back to {}",
+                msg.resource_name, name, next
+            );
+            send_from(chain, index + 1, msg)
+        }
+        None => Box::new(err(e)),
+    }))
+}
+
+impl NotifierSender for FallbackSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        send_from(self.chain.clone(), 0, msg)
+    }
+}
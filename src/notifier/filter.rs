@@ -0,0 +1,202 @@
+//! Filters, which decide whether message is delivered to particular recipient.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::notifier::{Message, MessageKind, Severity};
+
+/// Every set condition must match. Empty lists match everything.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct MessageFilter {
+    /// Globs (`*` and `?`) of resource names, any of them must match.
+    #[serde(default)]
+    resources: Vec<String>,
+    /// Globs of label values, all labels must match.
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    #[serde(default)]
+    states: Vec<MessageKind>,
+    #[serde(default)]
+    severities: Vec<Severity>,
+    /// Nothing is delivered within these hours.
+    quiet_hours: Option<QuietHours>,
+}
+
+impl MessageFilter {
+    pub(crate) fn matches(&self, msg: &Message) -> bool {
+        (self.resources.is_empty()
+            || self
+                .resources
+                .iter()
+                .any(|x| glob_match(x, &msg.resource_name)))
+            && self.labels.iter().all(|(k, v)| {
+                msg.info
+                    .labels
+                    .get(k)
+                    .map(|x| glob_match(v, x))
+                    .unwrap_or(false)
+            })
+            && (self.states.is_empty() || self.states.contains(&msg.kind))
+            && (self.severities.is_empty() || self.severities.contains(&msg.info.severity()))
+            && !self
+                .quiet_hours
+                .as_ref()
+                .map(|x| x.contains(msg.timestamp))
+                .unwrap_or(false)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct QuietHours {
+    /// Start of quiet hours (`HH:MM`).
+    #[serde(deserialize_with = "deserialize_time")]
+    from: NaiveTime,
+    /// End of quiet hours (`HH:MM`), may be less than `from` to span midnight.
+    #[serde(deserialize_with = "deserialize_time")]
+    to: NaiveTime,
+    /// IANA time zone of hours (`Europe/Berlin`), UTC by default.
+    #[serde(default = "utc")]
+    timezone: Tz,
+}
+
+impl QuietHours {
+    fn contains(&self, timestamp: DateTime<Utc>) -> bool {
+        let time = timestamp.with_timezone(&self.timezone).time();
+        if self.from <= self.to {
+            self.from <= time && time < self.to
+        } else {
+            self.from <= time || time < self.to
+        }
+    }
+}
+
+fn utc() -> Tz {
+    Tz::UTC
+}
+
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(&s, "%H:%M").map_err(serde::de::Error::custom)
+}
+
+/// Match text against pattern with `*` (any sequence) and `?` (any character) wildcards.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // Position after last `*` and text position it was matched against.
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bt)) => {
+                    p = bp;
+                    t = bt + 1;
+                    backtrack = Some((bp, bt + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quiet_hours(from: &str, to: &str, timezone: Tz) -> QuietHours {
+        QuietHours {
+            from: NaiveTime::parse_from_str(from, "%H:%M").unwrap(),
+            to: NaiveTime::parse_from_str(to, "%H:%M").unwrap(),
+            timezone,
+        }
+    }
+
+    fn utc_time(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn quiet_hours_within_day() {
+        let hours = quiet_hours("09:00", "17:00", Tz::UTC);
+        assert!(!hours.contains(utc_time("2024-01-15T08:59:00Z")));
+        assert!(hours.contains(utc_time("2024-01-15T09:00:00Z")));
+        assert!(!hours.contains(utc_time("2024-01-15T17:00:00Z")));
+    }
+
+    #[test]
+    fn quiet_hours_span_midnight() {
+        let hours = quiet_hours("22:00", "07:00", Tz::UTC);
+        assert!(hours.contains(utc_time("2024-01-15T23:30:00Z")));
+        assert!(hours.contains(utc_time("2024-01-15T06:59:00Z")));
+        assert!(!hours.contains(utc_time("2024-01-15T12:00:00Z")));
+    }
+
+    #[test]
+    fn quiet_hours_follow_daylight_saving_time() {
+        let hours = quiet_hours("22:00", "07:00", Tz::Europe__Berlin);
+        // 22:30 CET in winter and CEST in summer.
+        assert!(hours.contains(utc_time("2024-01-15T21:30:00Z")));
+        assert!(hours.contains(utc_time("2024-07-15T20:30:00Z")));
+        // 06:30 UTC is 07:30 CET in winter, but 08:30 CEST in summer.
+        assert!(!hours.contains(utc_time("2024-01-15T06:30:00Z")));
+        assert!(hours.contains(utc_time("2024-07-15T04:30:00Z")));
+        assert!(!hours.contains(utc_time("2024-07-15T05:30:00Z")));
+    }
+
+    #[test]
+    fn quiet_hours_timezone_is_iana_name() {
+        let hours: QuietHours =
+            serde_yaml::from_str("{from: \"22:00\", to: \"07:00\", timezone: America/New_York}")
+                .unwrap();
+        assert_eq!(hours.timezone, Tz::America__New_York);
+        let hours: QuietHours = serde_yaml::from_str("{from: \"22:00\", to: \"07:00\"}").unwrap();
+        assert_eq!(hours.timezone, Tz::UTC);
+        assert!(serde_yaml::from_str::<QuietHours>(
+            "{from: \"22:00\", to: \"07:00\", timezone: \"+03:00\"}"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn glob_match_literal() {
+        assert!(glob_match("db-1", "db-1"));
+        assert!(!glob_match("db-1", "db-12"));
+        assert!(!glob_match("db-12", "db-1"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "db"));
+    }
+
+    #[test]
+    fn glob_match_wildcards() {
+        assert!(glob_match("db-*", "db-1"));
+        assert!(glob_match("db-*", "db-"));
+        assert!(!glob_match("db-*", "web-db-1"));
+        assert!(glob_match("*-db-*", "eu-db-primary"));
+        assert!(glob_match("db-?", "db-1"));
+        assert!(!glob_match("db-?", "db-10"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("**", "anything"));
+    }
+
+    #[test]
+    fn glob_match_backtracks() {
+        assert!(glob_match("*ab", "aab"));
+        assert!(glob_match("a*b*c", "abbbc"));
+        assert!(glob_match("*a?c", "xabcabc"));
+        assert!(!glob_match("a*b*c", "abcb"));
+    }
+}
@@ -0,0 +1,200 @@
+//! Delivery of notifications over HTTP with timeout and retries.
+
+use std::time::{Duration, Instant};
+
+use reqwest::{
+    header::RETRY_AFTER,
+    r#async::{Client, ClientBuilder, RequestBuilder, Response},
+    StatusCode,
+};
+
+use futures::{
+    future::{loop_fn, ok, Either, Loop},
+    Future, Stream,
+};
+use tokio_timer::Delay;
+
+use log::warn;
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{notifier, BoxedFuture};
+
+#[derive(Debug, Fail)]
+pub(crate) enum HttpDeliveryError {
+    /// Description of `reqwest::Error` without URL, see `request_error`.
+    #[fail(display = "HTTP request error: {}", err)]
+    RequestError { err: String },
+    #[fail(display = "Unexpected HTTP code: {}", code)]
+    UnexpectedHttpCode { code: u16 },
+    #[fail(display = "Timer error: {}", err)]
+    TimerError { err: tokio_timer::Error },
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(crate) struct HttpDeliveryConfig {
+    /// Timeout of single request in milliseconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Number of retries after failed request.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Delay before first retry in milliseconds, doubled on every next retry up to 10 minutes.
+    #[serde(default = "default_retry_delay")]
+    pub retry_delay: u64,
+}
+
+fn default_timeout() -> u64 {
+    10000
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_retry_delay() -> u64 {
+    1000
+}
+
+/// HTTP client, which retries failed requests.
+#[derive(Clone)]
+pub(crate) struct HttpDelivery {
+    client: Client,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl HttpDelivery {
+    pub(crate) fn from_config(config: &HttpDeliveryConfig) -> Result<Self, reqwest::Error> {
+        let client = ClientBuilder::new()
+            .timeout(Duration::from_millis(config.timeout))
+            .build()?;
+        Ok(Self {
+            client,
+            retries: config.retries,
+            retry_delay: Duration::from_millis(config.retry_delay),
+        })
+    }
+
+    /// Send request, built by `make_request` for every attempt, until it succeeds or
+    /// retries are exhausted. Transport errors, 5xx, 408 and 429 codes are retried.
+    pub(crate) fn send<F>(&self, make_request: F) -> BoxedFuture<Response, HttpDeliveryError>
+    where
+        F: Fn(&Client) -> RequestBuilder + Send + 'static,
+    {
+        let client = self.client.clone();
+        let (retries, retry_delay) = (self.retries, self.retry_delay);
+        Box::new(loop_fn(0, move |attempt| {
+            make_request(&client)
+                .send()
+                .then(check_response)
+                .and_then(move |res| {
+                    let (err, delay) = match res {
+                        Ok(resp) => return Either::A(ok(Loop::Break(resp))),
+                        Err(RetryableError { err, .. }) if attempt >= retries => {
+                            return Either::A(futures::future::err(err));
+                        }
+                        Err(RetryableError { err, delay }) => (
+                            err,
+                            // Delay, requested by server, is capped as well.
+                            delay.map_or_else(
+                                || notifier::retry_delay(retry_delay, attempt),
+                                |x| x.min(notifier::MAX_RETRY_DELAY),
+                            ),
+                        ),
+                    };
+                    warn!(
+                        "HTTP delivery attempt {} failed ({}), retry in {:?}",
+                        attempt + 1,
+                        err,
+                        delay
+                    );
+                    Either::B(
+                        Delay::new(Instant::now() + delay)
+                            .map(move |_| Loop::Continue(attempt + 1))
+                            .map_err(|e| HttpDeliveryError::TimerError { err: e }),
+                    )
+                })
+        }))
+    }
+}
+
+/// Failure, after which request should be retried, possibly after delay requested by server.
+struct RetryableError {
+    err: HttpDeliveryError,
+    delay: Option<Duration>,
+}
+
+/// Split response into successful, retryable and fatal.
+fn check_response(
+    res: Result<Response, reqwest::Error>,
+) -> BoxedFuture<Result<Response, RetryableError>, HttpDeliveryError> {
+    let resp = match res {
+        Ok(resp) => resp,
+        Err(e) => {
+            return Box::new(ok(Err(RetryableError {
+                err: request_error(&e),
+                delay: None,
+            })))
+        }
+    };
+    let code = resp.status();
+    if code.is_success() {
+        return Box::new(ok(Ok(resp)));
+    }
+    let err = HttpDeliveryError::UnexpectedHttpCode {
+        code: code.as_u16(),
+    };
+    match code {
+        StatusCode::TOO_MANY_REQUESTS => match retry_after_header(&resp) {
+            Some(delay) => Box::new(ok(Err(RetryableError {
+                err,
+                delay: Some(delay),
+            }))),
+            None => Box::new(resp.into_body().concat2().then(move |body| {
+                Ok(Err(RetryableError {
+                    err,
+                    delay: body.ok().and_then(|x| retry_after_body(&x)),
+                }))
+            })),
+        },
+        StatusCode::REQUEST_TIMEOUT => Box::new(ok(Err(RetryableError { err, delay: None }))),
+        code if code.is_server_error() => Box::new(ok(Err(RetryableError { err, delay: None }))),
+        _ => Box::new(futures::future::err(err)),
+    }
+}
+
+/// Request error without URL, which may contain secrets (like bot token of Telegram or Slack
+/// webhook path), so it can be logged.
+fn request_error(err: &reqwest::Error) -> HttpDeliveryError {
+    let mut description = err.to_string();
+    if let Some(url) = err.url() {
+        let prefix = format!("{}: ", url);
+        if description.starts_with(&prefix) {
+            description.drain(..prefix.len());
+        }
+    }
+    HttpDeliveryError::RequestError { err: description }
+}
+
+/// Delay from `Retry-After` header (only delta-seconds form is supported).
+fn retry_after_header(resp: &Response) -> Option<Duration> {
+    resp.headers()
+        .get(RETRY_AFTER)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Delay in seconds from `retry_after` (Discord) or `parameters.retry_after` (Telegram)
+/// field of JSON body.
+fn retry_after_body(body: &[u8]) -> Option<Duration> {
+    let value: serde_json::Value = serde_json::from_slice(body).ok()?;
+    value
+        .get("retry_after")
+        .or_else(|| value.pointer("/parameters/retry_after"))
+        .and_then(|x| x.as_f64())
+        .map(|secs| Duration::from_millis((secs * 1000.0) as u64))
+}
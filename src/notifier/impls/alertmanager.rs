@@ -0,0 +1,258 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use reqwest::Url;
+
+use futures::Future;
+
+use log::debug;
+
+use serde::Deserialize;
+use serde_json::json;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{
+        delivery_error,
+        http::{HttpDelivery, HttpDeliveryConfig},
+        template, Message, MessageKind, Notifier, NotifierSender,
+    },
+    BoxedFuture,
+};
+
+#[derive(Debug, Fail)]
+enum AlertmanagerError {
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserialize { err: serde_yaml::Error },
+    #[fail(display = "Reqwest client error: {}", err)]
+    ReqwestClient { err: reqwest::Error },
+    #[fail(display = "Url parse error: {}", err)]
+    UrlParse { err: reqwest::UrlError },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct AlertmanagerConfig {
+    /// Alertmanager base URL.
+    url: String,
+    #[serde(default = "default_alert_name")]
+    alert_name: String,
+    /// Static labels, added to `alertname`, `resource` and labels of resource. They take
+    /// precedence over labels of resource.
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    /// Annotation templates, see `notifier::template`.
+    #[serde(default = "default_annotations")]
+    annotations: BTreeMap<String, String>,
+    generator_url: Option<String>,
+    /// Lifetime of firing alert in milliseconds. Alertmanager resolves alerts without `endsAt`,
+    /// which are not resent within its `resolve_timeout`, so it must cover expected outage
+    /// duration.
+    #[serde(default = "default_alert_ttl")]
+    alert_ttl: u64,
+    #[serde(flatten)]
+    delivery: HttpDeliveryConfig,
+}
+
+fn default_alert_name() -> String {
+    "SentinelResourceError".into()
+}
+
+fn default_alert_ttl() -> u64 {
+    // 7 days
+    604_800_000
+}
+
+fn default_annotations() -> BTreeMap<String, String> {
+    vec![
+        ("summary".to_string(), "{{title}}".to_string()),
+        ("description".to_string(), "{{description}}".to_string()),
+        ("state".to_string(), "{{state}}".to_string()),
+    ]
+    .into_iter()
+    .collect()
+}
+
+pub(crate) struct AlertmanagerNotifier {
+    sender: AlertmanagerSender,
+}
+
+impl Notifier for AlertmanagerNotifier {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        Box::new(self.sender.clone())
+    }
+
+    fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        let am_config: AlertmanagerConfig = serde_yaml::from_value(config).map_err(|e| {
+            Box::new(AlertmanagerError::YamlDeserialize { err: e }) as Box<dyn Fail>
+        })?;
+        let url = Url::parse(&format!(
+            "{}/api/v2/alerts",
+            am_config.url.trim_end_matches('/')
+        ))
+        .map_err(|e| Box::new(AlertmanagerError::UrlParse { err: e }) as Box<dyn Fail>)?;
+        let delivery = HttpDelivery::from_config(&am_config.delivery)
+            .map_err(|e| Box::new(AlertmanagerError::ReqwestClient { err: e }) as Box<dyn Fail>)?;
+        let mut labels = am_config.labels;
+        labels.insert("alertname".into(), am_config.alert_name);
+        Ok(Box::new(Self {
+            sender: AlertmanagerSender {
+                delivery,
+                url,
+                labels,
+                annotations: am_config.annotations,
+                generator_url: am_config.generator_url,
+                alert_ttl: Duration::from_millis(am_config.alert_ttl),
+            },
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct AlertmanagerSender {
+    delivery: HttpDelivery,
+    url: Url,
+    labels: BTreeMap<String, String>,
+    annotations: BTreeMap<String, String>,
+    generator_url: Option<String>,
+    alert_ttl: Duration,
+}
+
+impl AlertmanagerSender {
+    /// Alert is identified by its labels, so state goes to annotations, otherwise changed error
+    /// would be a new alert.
+    fn alert(&self, msg: &Message) -> serde_json::Value {
+        let mut labels = msg.info.labels.clone();
+        labels.extend(self.labels.clone());
+        labels.insert("resource".into(), msg.resource_name.clone());
+        let annotations = self
+            .annotations
+            .iter()
+            .map(|(k, v)| (k.clone(), template::render(v, msg)))
+            .collect::<BTreeMap<_, _>>();
+        let mut alert = json!({
+            "labels": labels,
+            "annotations": annotations,
+            "startsAt": msg.since.to_rfc3339(),
+        });
+        let ends_at = match msg.kind {
+            MessageKind::Resolved => Some(msg.timestamp),
+            MessageKind::New | MessageKind::Changed | MessageKind::Reminder => {
+                chrono::Duration::from_std(self.alert_ttl)
+                    .ok()
+                    .and_then(|x| msg.timestamp.checked_add_signed(x))
+            }
+        };
+        if let Some(ends_at) = ends_at {
+            alert["endsAt"] = ends_at.to_rfc3339().into();
+        }
+        if let Some(ref generator_url) = self.generator_url {
+            alert["generatorURL"] = generator_url.as_str().into();
+        }
+        alert
+    }
+
+    /// Alert of every resource of digest, as alerts are identified by resource.
+    fn payload(&self, msg: &Message) -> serde_json::Value {
+        msg.per_resource()
+            .iter()
+            .map(|x| self.alert(x))
+            .collect::<Vec<_>>()
+            .into()
+    }
+}
+
+impl NotifierSender for AlertmanagerSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let payload = self.payload(&msg);
+        debug!("Send Alertmanager alert: {}", payload);
+        let url = self.url.clone();
+        Box::new(
+            self.delivery
+                .send(move |client| client.post(url.clone()).json(&payload))
+                .map(|resp| debug!("AlertmanagerNotifier response: {}", resp.status()))
+                .map_err(|e| delivery_error("AlertmanagerNotifier", e)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::notifier::ResourceInfo;
+
+    fn sender(labels: &[(&str, &str)]) -> AlertmanagerSender {
+        let delivery = serde_yaml::from_str("{}").unwrap();
+        let mut labels = labels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<BTreeMap<_, _>>();
+        labels.insert("alertname".into(), default_alert_name());
+        AlertmanagerSender {
+            delivery: HttpDelivery::from_config(&delivery).unwrap(),
+            url: Url::parse("http://alertmanager:9093/api/v2/alerts").unwrap(),
+            labels,
+            annotations: default_annotations(),
+            generator_url: None,
+            alert_ttl: Duration::from_millis(default_alert_ttl()),
+        }
+    }
+
+    fn message(kind: MessageKind, labels: &[(&str, &str)]) -> Message {
+        Message::new(
+            "Error (new) db-1".into(),
+            String::new(),
+            kind,
+            "db-1".into(),
+            "Connection refused".into(),
+            Utc::now(),
+        )
+        .with_context(
+            Vec::new(),
+            ResourceInfo {
+                labels: labels
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+                ..ResourceInfo::default()
+            },
+        )
+    }
+
+    #[test]
+    fn labels_of_resource_are_merged() {
+        let sender = sender(&[("env", "prod"), ("team", "ops")]);
+        let msg = message(
+            MessageKind::New,
+            &[("team", "dba"), ("tier", "db"), ("resource", "other")],
+        );
+        let alert = sender.alert(&msg);
+        assert_eq!(
+            alert["labels"],
+            json!({
+                "alertname": "SentinelResourceError",
+                "env": "prod",
+                "resource": "db-1",
+                "team": "ops",
+                "tier": "db",
+            })
+        );
+        assert_eq!(alert["annotations"]["description"], "Connection refused");
+    }
+
+    #[test]
+    fn resolved_alert_ends_at_message() {
+        let sender = sender(&[]);
+        let msg = message(MessageKind::Resolved, &[]);
+        let alert = sender.alert(&msg);
+        assert_eq!(alert["endsAt"], msg.timestamp.to_rfc3339());
+        let msg = message(MessageKind::New, &[]);
+        let alert = sender.alert(&msg);
+        let ends_at = msg.timestamp + chrono::Duration::days(7);
+        assert_eq!(alert["endsAt"], ends_at.to_rfc3339());
+    }
+}
@@ -0,0 +1,118 @@
+use reqwest::Url;
+
+use futures::Future;
+
+use log::debug;
+
+use serde::Deserialize;
+use serde_json::json;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{
+        delivery_error,
+        http::{HttpDelivery, HttpDeliveryConfig},
+        Message, Notifier, NotifierSender,
+    },
+    BoxedFuture,
+};
+
+/// Discord limits of embed description and field value.
+const MAX_DESCRIPTION_LEN: usize = 4096;
+const MAX_FIELD_LEN: usize = 1024;
+
+#[derive(Debug, Fail)]
+enum DiscordError {
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserialize { err: serde_yaml::Error },
+    #[fail(display = "Reqwest client error: {}", err)]
+    ReqwestClient { err: reqwest::Error },
+    #[fail(display = "Url parse error: {}", err)]
+    UrlParse { err: reqwest::UrlError },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct DiscordConfig {
+    url: String,
+    /// Override of webhook's default username.
+    username: Option<String>,
+    #[serde(flatten)]
+    delivery: HttpDeliveryConfig,
+}
+
+pub(crate) struct DiscordNotifier {
+    sender: DiscordSender,
+}
+
+impl Notifier for DiscordNotifier {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        Box::new(self.sender.clone())
+    }
+
+    fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        let discord_config: DiscordConfig = serde_yaml::from_value(config)
+            .map_err(|e| Box::new(DiscordError::YamlDeserialize { err: e }) as Box<dyn Fail>)?;
+        let url = Url::parse(&discord_config.url)
+            .map_err(|e| Box::new(DiscordError::UrlParse { err: e }) as Box<dyn Fail>)?;
+        let delivery = HttpDelivery::from_config(&discord_config.delivery)
+            .map_err(|e| Box::new(DiscordError::ReqwestClient { err: e }) as Box<dyn Fail>)?;
+        Ok(Box::new(Self {
+            sender: DiscordSender {
+                delivery,
+                url,
+                username: discord_config.username,
+            },
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct DiscordSender {
+    delivery: HttpDelivery,
+    url: Url,
+    username: Option<String>,
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
+
+impl DiscordSender {
+    fn payload(&self, msg: &Message) -> serde_json::Value {
+        let mut payload = json!({
+            "embeds": [{
+                "title": msg.title,
+                "description": truncate(&msg.body, MAX_DESCRIPTION_LEN),
+                "color": msg.kind.color(),
+                "fields": [
+                    { "name": "Resource", "value": truncate(&msg.resource_name, MAX_FIELD_LEN), "inline": true },
+                    { "name": "State", "value": msg.kind.label(), "inline": true },
+                    { "name": "Error", "value": truncate(&msg.description, MAX_FIELD_LEN), "inline": false },
+                ],
+                "timestamp": msg.timestamp.to_rfc3339(),
+            }],
+        });
+        if let Some(ref username) = self.username {
+            payload["username"] = username.as_str().into();
+        }
+        payload
+    }
+}
+
+impl NotifierSender for DiscordSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let payload = self.payload(&msg);
+        debug!("Send Discord message: {}", payload);
+        let url = self.url.clone();
+        Box::new(
+            self.delivery
+                .send(move |client| client.post(url.clone()).json(&payload))
+                .map(|resp| debug!("DiscordNotifier response: {}", resp.status()))
+                .map_err(|e| delivery_error("DiscordNotifier", e)),
+        )
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    process::{Command, ExitStatus, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::Future;
+use tokio_process::CommandExt;
+use tokio_sync::semaphore::{AcquireError, Semaphore};
+use tokio_timer::Timeout;
+
+use log::debug;
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{delivery_error, limit::acquire, Message, Notifier, NotifierSender},
+    BoxedFuture,
+};
+
+#[derive(Debug, Fail)]
+enum ExecError {
+    // Delivery failures
+    #[fail(display = "Failed to spawn '{}': {}", command, err)]
+    SpawnError { command: String, err: io::Error },
+    #[fail(display = "Failed to write stdin of '{}': {}", command, err)]
+    StdinError { command: String, err: io::Error },
+    #[fail(display = "'{}' failed: {}", command, err)]
+    WaitError { command: String, err: io::Error },
+    #[fail(display = "'{}' exited with {}: {}", command, status, stderr)]
+    NonZeroExit {
+        command: String,
+        status: ExitStatus,
+        stderr: String,
+    },
+    #[fail(display = "'{}' timed out", command)]
+    Timeout { command: String },
+    #[fail(display = "Timer error: {}", err)]
+    TimerError { err: tokio_timer::Error },
+    #[fail(display = "Concurrency limiter closed: {}", err)]
+    LimiterError { err: AcquireError },
+
+    // Build failures
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserializeError { err: serde_yaml::Error },
+    #[fail(display = "'max_concurrency' must be positive")]
+    InvalidConcurrency,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ExecConfig {
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+    /// Additional environment variables.
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    /// Milliseconds, after which command is killed.
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+    /// Maximum number of simultaneously running commands, the rest wait in queue.
+    #[serde(default = "default_max_concurrency")]
+    max_concurrency: usize,
+}
+
+fn default_timeout() -> u64 {
+    30_000
+}
+
+fn default_max_concurrency() -> usize {
+    4
+}
+
+pub(crate) struct ExecNotifier {
+    sender: ExecSender,
+}
+
+impl Notifier for ExecNotifier {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        Box::new(self.sender.clone())
+    }
+
+    fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        let exec_config: ExecConfig = serde_yaml::from_value(config)
+            .map_err(|e| Box::new(ExecError::YamlDeserializeError { err: e }) as Box<dyn Fail>)?;
+        if exec_config.max_concurrency == 0 {
+            return Err(Box::new(ExecError::InvalidConcurrency));
+        }
+        Ok(Box::new(Self {
+            sender: ExecSender {
+                command: exec_config.command,
+                args: exec_config.args,
+                env: exec_config.env,
+                timeout: Duration::from_millis(exec_config.timeout),
+                semaphore: Arc::new(Semaphore::new(exec_config.max_concurrency)),
+            },
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ExecSender {
+    command: String,
+    args: Vec<String>,
+    env: BTreeMap<String, String>,
+    timeout: Duration,
+    semaphore: Arc<Semaphore>,
+}
+
+impl ExecSender {
+    /// Spawn command with message in environment and on stdin, and wait for its exit.
+    fn run(&self, msg: &Message) -> BoxedFuture<(), ExecError> {
+        let mut command = Command::new(&self.command);
+        command
+            .args(&self.args)
+            .envs(&self.env)
+            .env("SENTINEL_RESOURCE", &msg.resource_name)
+            .env("SENTINEL_STATE", msg.kind.as_str())
+            .env("SENTINEL_TITLE", &msg.title)
+            .env("SENTINEL_BODY", &msg.body)
+            .env("SENTINEL_DESCRIPTION", &msg.description)
+            .env("SENTINEL_TIMESTAMP", msg.timestamp.to_rfc3339())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        let name = self.command.clone();
+        let mut child = match command.spawn_async() {
+            Ok(x) => x,
+            Err(e) => {
+                return Box::new(futures::future::err(ExecError::SpawnError {
+                    command: name,
+                    err: e,
+                }))
+            }
+        };
+        let input = msg.to_json().to_string();
+        let stdin = child.stdin().take().expect("stdin is piped");
+        let (name1, name2, name3) = (name.clone(), name.clone(), name.clone());
+        let fut = tokio::io::write_all(stdin, input)
+            .map(|_| ())
+            .or_else(move |e| match e.kind() {
+                // Command is not interested in stdin.
+                io::ErrorKind::BrokenPipe => Ok(()),
+                _ => Err(ExecError::StdinError {
+                    command: name1,
+                    err: e,
+                }),
+            })
+            .and_then(move |_| {
+                child.wait_with_output().map_err(|e| ExecError::WaitError {
+                    command: name2,
+                    err: e,
+                })
+            })
+            .and_then(move |output| {
+                if output.status.success() {
+                    Ok(())
+                } else {
+                    Err(ExecError::NonZeroExit {
+                        command: name3,
+                        status: output.status,
+                        stderr: String::from_utf8_lossy(&output.stderr).trim().into(),
+                    })
+                }
+            });
+        // Child is killed, when dropped on timeout.
+        Box::new(Timeout::new(fut, self.timeout).map_err(move |e| {
+            if e.is_elapsed() {
+                ExecError::Timeout { command: name }
+            } else if e.is_inner() {
+                e.into_inner().unwrap()
+            } else {
+                ExecError::TimerError {
+                    err: e.into_timer().unwrap(),
+                }
+            }
+        }))
+    }
+}
+
+impl NotifierSender for ExecSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let sender = self.clone();
+        Box::new(
+            acquire(self.semaphore.clone())
+                .map_err(|e| ExecError::LimiterError { err: e })
+                .and_then(move |permit| {
+                    debug!("Run '{}' for {}", sender.command, msg.resource_name);
+                    sender.run(&msg).then(move |res| {
+                        drop(permit);
+                        res
+                    })
+                })
+                .map_err(|e| delivery_error("ExecNotifier", e)),
+        )
+    }
+}
@@ -0,0 +1,346 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use futures::Future;
+
+use log::debug;
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{
+    blocking,
+    notifier::{delivery_error, Message, Notifier, NotifierSender},
+    BoxedFuture,
+};
+
+#[derive(Debug, Fail)]
+enum FileError {
+    // Delivery failures
+    #[fail(display = "IO error on '{}': {}", path, err)]
+    Io { path: String, err: io::Error },
+    #[fail(display = "Blocking error: {}", err)]
+    Blocking {
+        err: tokio_threadpool::BlockingError,
+    },
+
+    // Build failures
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserialize { err: serde_yaml::Error },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct FileConfig {
+    path: PathBuf,
+    /// Rotate, when file would exceed this size in bytes.
+    rotate_size: Option<u64>,
+    /// Rotate on first write of new (UTC) day.
+    #[serde(default)]
+    rotate_daily: bool,
+    /// Number of rotated files to keep, 0 keeps all of them.
+    #[serde(default = "default_keep")]
+    keep: usize,
+    /// Sync file to disk after every message.
+    #[serde(default)]
+    fsync: bool,
+}
+
+fn default_keep() -> usize {
+    7
+}
+
+pub(crate) struct FileNotifier {
+    sender: FileSender,
+}
+
+impl Notifier for FileNotifier {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        Box::new(self.sender.clone())
+    }
+
+    fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        let file_config: FileConfig = serde_yaml::from_value(config)
+            .map_err(|e| Box::new(FileError::YamlDeserialize { err: e }) as Box<dyn Fail>)?;
+        // Open file right away, so misconfigured path fails on start.
+        let log = EventLog::open(file_config).map_err(|e| Box::new(e) as Box<dyn Fail>)?;
+        Ok(Box::new(Self {
+            sender: FileSender {
+                log: Arc::new(Mutex::new(log)),
+            },
+        }))
+    }
+}
+
+/// Appended file with its current size and date of first record.
+struct EventLog {
+    config: FileConfig,
+    file: File,
+    size: u64,
+    date: NaiveDate,
+}
+
+impl EventLog {
+    fn open(config: FileConfig) -> Result<Self, FileError> {
+        let path = config.path.clone();
+        let io_err = |e| FileError::Io {
+            path: path.display().to_string(),
+            err: e,
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(io_err)?;
+        let metadata = file.metadata().map_err(io_err)?;
+        let date = if metadata.len() > 0 {
+            metadata
+                .modified()
+                .map(|x| DateTime::<Utc>::from(x).date_naive())
+                .unwrap_or_else(|_| Utc::now().date_naive())
+        } else {
+            Utc::now().date_naive()
+        };
+        Ok(Self {
+            size: metadata.len(),
+            config,
+            file,
+            date,
+        })
+    }
+
+    fn write(&mut self, line: &[u8], now: DateTime<Utc>) -> io::Result<()> {
+        let new_day = self.config.rotate_daily && now.date_naive() != self.date;
+        let too_big = self
+            .config
+            .rotate_size
+            .map(|max| self.size > 0 && self.size + line.len() as u64 > max)
+            .unwrap_or(false);
+        if new_day || too_big {
+            self.rotate(now)?;
+        }
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+        if self.config.fsync {
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Rename current file to `<path>.<date>` (with `.<n>` suffix, if taken), reopen it and
+    /// remove rotated files beyond `keep`.
+    fn rotate(&mut self, now: DateTime<Utc>) -> io::Result<()> {
+        let path = &self.config.path;
+        let base = format!("{}.{}", path.display(), self.date.format("%Y-%m-%d"));
+        let mut rotated = PathBuf::from(&base);
+        let mut n = 0;
+        while rotated.exists() {
+            n += 1;
+            rotated = PathBuf::from(format!("{}.{}", base, n));
+        }
+        debug!("Rotate {} to {}", path.display(), rotated.display());
+        fs::rename(path, &rotated)?;
+        self.file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.size = 0;
+        self.date = now.date_naive();
+        if self.config.keep > 0 {
+            remove_old(path, self.config.keep)?;
+        }
+        Ok(())
+    }
+}
+
+/// Remove oldest rotated files of `path`, so `keep` of them remain.
+fn remove_old(path: &Path, keep: usize) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(x) if !x.as_os_str().is_empty() => x,
+        _ => Path::new("."),
+    };
+    let prefix = match path.file_name() {
+        Some(x) => format!("{}.", x.to_string_lossy()),
+        None => return Ok(()),
+    };
+    let mut rotated = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .filter(|x| x.file_name().to_string_lossy().starts_with(&prefix))
+        .filter_map(|x| {
+            let modified = x.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, x.path()))
+        })
+        .collect::<Vec<_>>();
+    if rotated.len() <= keep {
+        return Ok(());
+    }
+    rotated.sort();
+    for (_, old) in &rotated[..rotated.len() - keep] {
+        debug!("Remove rotated {}", old.display());
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+pub(crate) struct FileSender {
+    log: Arc<Mutex<EventLog>>,
+}
+
+impl NotifierSender for FileSender {
+    /// Digest is written as record of each of its resources, so log can be queried by resource.
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let records = msg
+            .per_resource()
+            .iter()
+            .map(|x| (format!("{}\n", x.to_json()), x.timestamp))
+            .collect::<Vec<_>>();
+        let log = self.log.clone();
+        Box::new(
+            blocking(move || {
+                let mut log = log.lock().unwrap();
+                records
+                    .iter()
+                    .try_for_each(|(line, timestamp)| log.write(line.as_bytes(), *timestamp))
+                    .map_err(|e| FileError::Io {
+                        path: log.config.path.display().to_string(),
+                        err: e,
+                    })
+            })
+            .map_err(|e| FileError::Blocking { err: e })
+            .and_then(|res| res)
+            .map_err(|e| delivery_error("FileNotifier", e)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+    use crate::notifier::MessageKind;
+
+    /// Empty directory, unique for test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("sentinel-file-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn config(path: PathBuf, rotate_size: Option<u64>, keep: usize) -> FileConfig {
+        FileConfig {
+            path,
+            rotate_size,
+            rotate_daily: false,
+            keep,
+            fsync: false,
+        }
+    }
+
+    /// Names of files in directory, sorted.
+    fn files(dir: &Path) -> Vec<String> {
+        let mut files = fs::read_dir(dir)
+            .unwrap()
+            .map(|x| x.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
+    fn message(resource: &str, kind: MessageKind) -> Message {
+        Message::new(
+            format!("{} {}", kind.label(), resource),
+            String::new(),
+            kind,
+            resource.into(),
+            String::new(),
+            Utc::now(),
+        )
+    }
+
+    #[test]
+    fn file_is_rotated_by_size() {
+        let dir = test_dir("size");
+        let path = dir.join("events.jsonl");
+        let mut log = EventLog::open(config(path.clone(), Some(100), 2)).unwrap();
+        let line = [b'x'; 40];
+        let now = Utc::now();
+        log.write(&line, now).unwrap();
+        log.write(&line, now).unwrap();
+        assert_eq!(files(&dir), vec!["events.jsonl"]);
+        // Third line would exceed limit.
+        log.write(&line, now).unwrap();
+        let date = format!("events.jsonl.{}", now.format("%Y-%m-%d"));
+        assert_eq!(files(&dir), vec!["events.jsonl".to_string(), date.clone()]);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 40);
+        assert_eq!(fs::metadata(dir.join(&date)).unwrap().len(), 80);
+        for _ in 0..4 {
+            log.write(&line, now).unwrap();
+        }
+        // Oldest rotated file is removed beyond `keep`.
+        assert_eq!(
+            files(&dir),
+            vec![
+                "events.jsonl".to_string(),
+                format!("{}.1", date),
+                format!("{}.2", date)
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn line_over_limit_is_written_to_empty_file() {
+        let dir = test_dir("large");
+        let path = dir.join("events.jsonl");
+        let mut log = EventLog::open(config(path.clone(), Some(10), 0)).unwrap();
+        log.write(&[b'x'; 40], Utc::now()).unwrap();
+        assert_eq!(files(&dir), vec!["events.jsonl"]);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 40);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn digest_is_written_per_resource() {
+        let dir = test_dir("digest");
+        let path = dir.join("events.jsonl");
+        let log = EventLog::open(config(path.clone(), None, 0)).unwrap();
+        let sender = FileSender {
+            log: Arc::new(Mutex::new(log)),
+        };
+        let digest = Message::combine(
+            vec![
+                message("db-1", MessageKind::New),
+                message("web-2", MessageKind::New),
+                message("db-1", MessageKind::Resolved),
+            ],
+            |parts| parts[0].clone(),
+        );
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(sender.send_message(digest)).unwrap();
+        let records = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|x| {
+                let record: serde_json::Value = serde_json::from_str(x).unwrap();
+                (record["resource"].clone(), record["state"].clone())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            records,
+            vec![
+                ("web-2".into(), "new".into()),
+                ("db-1".into(), "resolved".into())
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
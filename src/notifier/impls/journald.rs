@@ -0,0 +1,118 @@
+use std::{io, path::PathBuf};
+
+use futures::Future;
+use tokio::net::UnixDatagram;
+
+use log::debug;
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{delivery_error, syslog::severity, Message, Notifier, NotifierSender},
+    BoxedFuture,
+};
+
+#[derive(Debug, Fail)]
+enum JournaldError {
+    // Delivery failures
+    #[fail(display = "IO error: {}", err)]
+    IoError { err: io::Error },
+
+    // Build failures
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserializeError { err: serde_yaml::Error },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct JournaldConfig {
+    #[serde(default = "default_socket")]
+    socket: PathBuf,
+    #[serde(default = "default_identifier")]
+    identifier: String,
+}
+
+fn default_socket() -> PathBuf {
+    "/run/systemd/journal/socket".into()
+}
+
+fn default_identifier() -> String {
+    "sentinel".into()
+}
+
+pub(crate) struct JournaldNotifier {
+    sender: JournaldSender,
+}
+
+impl Notifier for JournaldNotifier {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        Box::new(self.sender.clone())
+    }
+
+    fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        let journald_config: JournaldConfig = serde_yaml::from_value(config).map_err(|e| {
+            Box::new(JournaldError::YamlDeserializeError { err: e }) as Box<dyn Fail>
+        })?;
+        Ok(Box::new(Self {
+            sender: JournaldSender {
+                socket: journald_config.socket,
+                identifier: journald_config.identifier,
+            },
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct JournaldSender {
+    socket: PathBuf,
+    identifier: String,
+}
+
+/// Append field in journal native format. Values with newlines are length-prefixed.
+fn push_field(buf: &mut Vec<u8>, name: &str, value: &str) {
+    buf.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        buf.push(b'\n');
+        buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        buf.push(b'=');
+    }
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(b'\n');
+}
+
+impl JournaldSender {
+    fn format(&self, msg: &Message) -> Vec<u8> {
+        let mut buf = Vec::new();
+        push_field(
+            &mut buf,
+            "MESSAGE",
+            &format!("{}: {}", msg.title, msg.description),
+        );
+        push_field(&mut buf, "PRIORITY", &severity(msg.kind).to_string());
+        push_field(&mut buf, "SYSLOG_IDENTIFIER", &self.identifier);
+        push_field(&mut buf, "SENTINEL_RESOURCE", &msg.resource_name);
+        push_field(&mut buf, "SENTINEL_STATE", msg.kind.as_str());
+        push_field(&mut buf, "SENTINEL_DESCRIPTION", &msg.description);
+        push_field(&mut buf, "SENTINEL_BODY", &msg.body);
+        buf
+    }
+}
+
+impl NotifierSender for JournaldSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let entry = self.format(&msg);
+        debug!("Send journal entry for {}", msg.resource_name);
+        let path = self.socket.clone();
+        Box::new(
+            futures::future::result(UnixDatagram::unbound())
+                .and_then(move |socket| socket.send_dgram(entry, path))
+                .map(|_| ())
+                .map_err(|e| delivery_error("JournaldNotifier", JournaldError::IoError { err: e })),
+        )
+    }
+}
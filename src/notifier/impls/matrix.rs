@@ -0,0 +1,163 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use reqwest::Url;
+
+use futures::Future;
+
+use log::{debug, error};
+
+use serde::Deserialize;
+use serde_json::json;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{
+        http::{HttpDelivery, HttpDeliveryConfig},
+        join_deliveries, template, Message, Notifier, NotifierSender,
+    },
+    BoxedFuture,
+};
+
+/// Makes transaction IDs unique within process.
+static TXN_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Fail)]
+enum MatrixError {
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserialize { err: serde_yaml::Error },
+    #[fail(display = "Reqwest client error: {}", err)]
+    ReqwestClient { err: reqwest::Error },
+    #[fail(display = "Url parse error: {}", err)]
+    UrlParse { err: reqwest::UrlError },
+    #[fail(display = "Url '{}' can't be homeserver URL", url)]
+    InvalidHomeserver { url: String },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct MatrixConfig {
+    homeserver: String,
+    access_token: String,
+    /// IDs (not aliases) of rooms, which bot has joined.
+    rooms: Vec<String>,
+    /// Send `m.notice` (not highlighted by clients) instead of `m.text`.
+    #[serde(default = "default_notice")]
+    notice: bool,
+    #[serde(flatten)]
+    delivery: HttpDeliveryConfig,
+}
+
+fn default_notice() -> bool {
+    true
+}
+
+pub(crate) struct MatrixNotifier {
+    sender: MatrixSender,
+}
+
+impl Notifier for MatrixNotifier {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        Box::new(self.sender.clone())
+    }
+
+    fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        let matrix_config: MatrixConfig = serde_yaml::from_value(config)
+            .map_err(|e| Box::new(MatrixError::YamlDeserialize { err: e }) as Box<dyn Fail>)?;
+        let homeserver = Url::parse(&matrix_config.homeserver)
+            .map_err(|e| Box::new(MatrixError::UrlParse { err: e }) as Box<dyn Fail>)?;
+        if homeserver.cannot_be_a_base() {
+            return Err(Box::new(MatrixError::InvalidHomeserver {
+                url: matrix_config.homeserver,
+            }));
+        }
+        let delivery = HttpDelivery::from_config(&matrix_config.delivery)
+            .map_err(|e| Box::new(MatrixError::ReqwestClient { err: e }) as Box<dyn Fail>)?;
+        Ok(Box::new(Self {
+            sender: MatrixSender {
+                delivery,
+                homeserver,
+                access_token: matrix_config.access_token,
+                rooms: matrix_config.rooms,
+                notice: matrix_config.notice,
+            },
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct MatrixSender {
+    delivery: HttpDelivery,
+    homeserver: Url,
+    access_token: String,
+    rooms: Vec<String>,
+    notice: bool,
+}
+
+impl MatrixSender {
+    /// `PUT` URL of event. Transaction ID is fixed for all attempts, so homeserver
+    /// deduplicates retried requests.
+    fn event_url(&self, room: &str, msg: &Message) -> Url {
+        let txn_id = format!(
+            "sentinel.{}.{}",
+            msg.timestamp.timestamp_millis(),
+            TXN_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut url = self.homeserver.clone();
+        url.path_segments_mut()
+            .expect("checked on creation")
+            .pop_if_empty()
+            .extend(&[
+                "_matrix",
+                "client",
+                "r0",
+                "rooms",
+                room,
+                "send",
+                "m.room.message",
+                &txn_id,
+            ]);
+        url
+    }
+
+    fn payload(&self, msg: &Message) -> serde_json::Value {
+        let formatted_body = format!(
+            "<p><font color=\"#{:06x}\"><b>{}</b></font></p><p>{}</p>",
+            msg.kind.color(),
+            template::escape_html(&msg.title),
+            template::escape_html(&msg.body).replace('\n', "<br/>")
+        );
+        json!({
+            "msgtype": if self.notice { "m.notice" } else { "m.text" },
+            "body": format!("{}\n{}", msg.title, msg.body),
+            "format": "org.matrix.custom.html",
+            "formatted_body": formatted_body,
+        })
+    }
+}
+
+impl NotifierSender for MatrixSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let payload = self.payload(&msg);
+        debug!("Send Matrix message: {}", payload);
+        let sends = self
+            .rooms
+            .iter()
+            .map(|room| {
+                let url = self.event_url(room, &msg);
+                let (payload, token) = (payload.clone(), self.access_token.clone());
+                let room = room.clone();
+                self.delivery
+                    .send(move |client| client.put(url.clone()).bearer_auth(&token).json(&payload))
+                    .map(|resp| debug!("MatrixNotifier response: {}", resp.status()))
+                    .map_err(move |e| {
+                        error!("MatrixNotifier error (room {}): {}", room, e);
+                        Box::new(e) as Box<dyn Fail>
+                    })
+            })
+            .collect::<Vec<_>>();
+        join_deliveries(sends)
+    }
+}
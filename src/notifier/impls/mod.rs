@@ -1 +1,13 @@
+pub(crate) mod alertmanager;
+pub(crate) mod discord;
+pub(crate) mod exec;
+pub(crate) mod file;
+pub(crate) mod journald;
+pub(crate) mod matrix;
+pub(crate) mod pagerduty;
+pub(crate) mod slack;
 pub(crate) mod smtp;
+pub(crate) mod syslog;
+pub(crate) mod teams;
+pub(crate) mod telegram;
+pub(crate) mod webhook;
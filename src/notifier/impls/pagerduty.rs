@@ -0,0 +1,214 @@
+use reqwest::Url;
+
+use futures::Future;
+
+use log::debug;
+
+use serde::Deserialize;
+use serde_json::json;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{
+        delivery_error,
+        http::{HttpDelivery, HttpDeliveryConfig},
+        join_deliveries, Message, MessageKind, Notifier, NotifierSender, Severity,
+    },
+    BoxedFuture,
+};
+
+/// PagerDuty limits event summary to 1024 characters.
+const MAX_SUMMARY_LEN: usize = 1024;
+
+#[derive(Debug, Fail)]
+enum PagerDutyError {
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserialize { err: serde_yaml::Error },
+    #[fail(display = "Reqwest client error: {}", err)]
+    ReqwestClient { err: reqwest::Error },
+    #[fail(display = "Url parse error: {}", err)]
+    UrlParse { err: reqwest::UrlError },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct PagerDutyConfig {
+    /// Integration key of Events API v2 service integration.
+    routing_key: String,
+    /// Severity of events about resources, which don't set their own.
+    #[serde(default)]
+    severity: Severity,
+    /// Prefix of `dedup_key`, which is followed by resource name.
+    #[serde(default = "default_dedup_key_prefix")]
+    dedup_key_prefix: String,
+    /// Events API base URL.
+    #[serde(default = "default_api_url")]
+    api_url: String,
+    #[serde(flatten)]
+    delivery: HttpDeliveryConfig,
+}
+
+fn default_dedup_key_prefix() -> String {
+    "sentinel/".into()
+}
+
+fn default_api_url() -> String {
+    "https://events.pagerduty.com".into()
+}
+
+pub(crate) struct PagerDutyNotifier {
+    sender: PagerDutySender,
+}
+
+impl Notifier for PagerDutyNotifier {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        Box::new(self.sender.clone())
+    }
+
+    fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        let pagerduty_config: PagerDutyConfig = serde_yaml::from_value(config)
+            .map_err(|e| Box::new(PagerDutyError::YamlDeserialize { err: e }) as Box<dyn Fail>)?;
+        let url = Url::parse(&format!(
+            "{}/v2/enqueue",
+            pagerduty_config.api_url.trim_end_matches('/')
+        ))
+        .map_err(|e| Box::new(PagerDutyError::UrlParse { err: e }) as Box<dyn Fail>)?;
+        let delivery = HttpDelivery::from_config(&pagerduty_config.delivery)
+            .map_err(|e| Box::new(PagerDutyError::ReqwestClient { err: e }) as Box<dyn Fail>)?;
+        Ok(Box::new(Self {
+            sender: PagerDutySender {
+                delivery,
+                url,
+                routing_key: pagerduty_config.routing_key,
+                severity: pagerduty_config.severity,
+                dedup_key_prefix: pagerduty_config.dedup_key_prefix,
+            },
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct PagerDutySender {
+    delivery: HttpDelivery,
+    url: Url,
+    routing_key: String,
+    severity: Severity,
+    dedup_key_prefix: String,
+}
+
+impl PagerDutySender {
+    /// Trigger event for new, changed or still active error, resolve event otherwise. Both use
+    /// the same `dedup_key`, so resolve closes incident opened by trigger.
+    fn payload(&self, msg: &Message) -> serde_json::Value {
+        let dedup_key = format!("{}{}", self.dedup_key_prefix, msg.resource_name);
+        match msg.kind {
+            MessageKind::New | MessageKind::Changed | MessageKind::Reminder => {
+                let summary: String = msg.title.chars().take(MAX_SUMMARY_LEN).collect();
+                json!({
+                    "routing_key": self.routing_key,
+                    "event_action": "trigger",
+                    "dedup_key": dedup_key,
+                    "payload": {
+                        "summary": summary,
+                        "source": msg.resource_name,
+                        "severity": msg.info.severity.unwrap_or(self.severity).as_str(),
+                        "timestamp": msg.timestamp.to_rfc3339(),
+                        "custom_details": {
+                            "state": msg.kind.as_str(),
+                            "description": msg.description,
+                            "body": msg.body,
+                        },
+                    },
+                })
+            }
+            MessageKind::Resolved => json!({
+                "routing_key": self.routing_key,
+                "event_action": "resolve",
+                "dedup_key": dedup_key,
+            }),
+        }
+    }
+}
+
+impl NotifierSender for PagerDutySender {
+    /// Digest is split into events about each of its resources, as incidents are tracked by
+    /// resource.
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let events = msg
+            .per_resource()
+            .iter()
+            .map(|msg| {
+                let payload = self.payload(msg);
+                debug!("Send PagerDuty event: {}", payload);
+                let url = self.url.clone();
+                self.delivery
+                    .send(move |client| client.post(url.clone()).json(&payload))
+                    .map(|resp| debug!("PagerDutyNotifier response: {}", resp.status()))
+                    .map_err(|e| delivery_error("PagerDutyNotifier", e))
+            })
+            .collect::<Vec<_>>();
+        join_deliveries(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::notifier::ResourceInfo;
+
+    fn sender(severity: Severity) -> PagerDutySender {
+        let delivery = serde_yaml::from_str("{}").unwrap();
+        PagerDutySender {
+            delivery: HttpDelivery::from_config(&delivery).unwrap(),
+            url: Url::parse("https://events.pagerduty.com/v2/enqueue").unwrap(),
+            routing_key: "key".into(),
+            severity,
+            dedup_key_prefix: "sentinel/".into(),
+        }
+    }
+
+    fn message(kind: MessageKind, severity: Option<Severity>) -> Message {
+        Message::new(
+            "Error (new) db-1".into(),
+            String::new(),
+            kind,
+            "db-1".into(),
+            String::new(),
+            Utc::now(),
+        )
+        .with_context(
+            Vec::new(),
+            ResourceInfo {
+                severity,
+                ..ResourceInfo::default()
+            },
+        )
+    }
+
+    #[test]
+    fn severity_of_resource_is_preferred() {
+        let sender = sender(Severity::Warning);
+        let payload = sender.payload(&message(MessageKind::New, Some(Severity::Critical)));
+        assert_eq!(payload["payload"]["severity"], "critical");
+        let payload = sender.payload(&message(MessageKind::New, Some(Severity::Info)));
+        assert_eq!(payload["payload"]["severity"], "info");
+        let payload = sender.payload(&message(MessageKind::New, None));
+        assert_eq!(payload["payload"]["severity"], "warning");
+    }
+
+    #[test]
+    fn resolve_uses_dedup_key_of_trigger() {
+        let sender = sender(Severity::Error);
+        let trigger = sender.payload(&message(MessageKind::New, None));
+        let resolve = sender.payload(&message(MessageKind::Resolved, None));
+        assert_eq!(trigger["event_action"], "trigger");
+        assert_eq!(resolve["event_action"], "resolve");
+        assert_eq!(trigger["dedup_key"], "sentinel/db-1");
+        assert_eq!(resolve["dedup_key"], trigger["dedup_key"]);
+    }
+}
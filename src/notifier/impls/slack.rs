@@ -0,0 +1,121 @@
+use reqwest::Url;
+
+use futures::Future;
+
+use log::debug;
+
+use serde::Deserialize;
+use serde_json::json;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{
+        delivery_error,
+        http::{HttpDelivery, HttpDeliveryConfig},
+        Message, Notifier, NotifierSender,
+    },
+    BoxedFuture,
+};
+
+#[derive(Debug, Fail)]
+enum SlackError {
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserialize { err: serde_yaml::Error },
+    #[fail(display = "Reqwest client error: {}", err)]
+    ReqwestClient { err: reqwest::Error },
+    #[fail(display = "Url parse error: {}", err)]
+    UrlParse { err: reqwest::UrlError },
+}
+
+/// Config of Slack or Mattermost incoming webhook.
+#[derive(Clone, Debug, Deserialize)]
+struct SlackConfig {
+    url: String,
+    /// Override of webhook's default channel.
+    channel: Option<String>,
+    /// Override of webhook's default username.
+    username: Option<String>,
+    #[serde(flatten)]
+    delivery: HttpDeliveryConfig,
+}
+
+pub(crate) struct SlackNotifier {
+    sender: SlackSender,
+}
+
+impl Notifier for SlackNotifier {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        Box::new(self.sender.clone())
+    }
+
+    fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        let slack_config: SlackConfig = serde_yaml::from_value(config)
+            .map_err(|e| Box::new(SlackError::YamlDeserialize { err: e }) as Box<dyn Fail>)?;
+        let url = Url::parse(&slack_config.url)
+            .map_err(|e| Box::new(SlackError::UrlParse { err: e }) as Box<dyn Fail>)?;
+        let delivery = HttpDelivery::from_config(&slack_config.delivery)
+            .map_err(|e| Box::new(SlackError::ReqwestClient { err: e }) as Box<dyn Fail>)?;
+        Ok(Box::new(Self {
+            sender: SlackSender {
+                delivery,
+                url,
+                channel: slack_config.channel,
+                username: slack_config.username,
+            },
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SlackSender {
+    delivery: HttpDelivery,
+    url: Url,
+    channel: Option<String>,
+    username: Option<String>,
+}
+
+impl SlackSender {
+    fn payload(&self, msg: &Message) -> serde_json::Value {
+        let mut payload = json!({
+            "attachments": [{
+                "fallback": msg.title,
+                "color": format!("#{:06x}", msg.kind.color()),
+                "title": msg.title,
+                "text": msg.body,
+                "fields": [
+                    { "title": "Resource", "value": msg.resource_name, "short": true },
+                    { "title": "State", "value": msg.kind.label(), "short": true },
+                    { "title": "Error", "value": msg.description, "short": false },
+                    { "title": "Since", "value": msg.since.to_rfc3339(), "short": true },
+                    { "title": "Time", "value": msg.timestamp.to_rfc3339(), "short": true },
+                ],
+                "ts": msg.timestamp.timestamp(),
+            }],
+        });
+        if let Some(ref channel) = self.channel {
+            payload["channel"] = channel.as_str().into();
+        }
+        if let Some(ref username) = self.username {
+            payload["username"] = username.as_str().into();
+        }
+        payload
+    }
+}
+
+impl NotifierSender for SlackSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let payload = self.payload(&msg);
+        debug!("Send Slack message: {}", payload);
+        let url = self.url.clone();
+        Box::new(
+            self.delivery
+                .send(move |client| client.post(url.clone()).json(&payload))
+                .map(|resp| debug!("SlackNotifier response: {}", resp.status()))
+                .map_err(|e| delivery_error("SlackNotifier", e)),
+        )
+    }
+}
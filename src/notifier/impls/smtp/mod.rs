@@ -0,0 +1,709 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use lettre::{
+    smtp::authentication::{Credentials, Mechanism},
+    smtp::client::net::ClientTlsParameters,
+    smtp::error::{Error as LettreSmtpError, SmtpResult},
+    smtp::ConnectionReuseParameters,
+    ClientSecurity, SmtpClient, SmtpTransport, Transport,
+};
+use lettre_email::Mailbox;
+use native_tls::TlsConnector;
+
+use futures::{
+    future::{err, lazy, loop_fn, ok, Either, Loop},
+    sync::oneshot,
+    Future, Stream,
+};
+use tokio_sync::semaphore::{AcquireError, Semaphore};
+use tokio_threadpool::BlockingError;
+use tokio_timer::{Delay, Interval};
+
+use log::{debug, error, info, warn};
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{
+    blocking,
+    notifier::{
+        self, delivery_error, filter::MessageFilter, join_deliveries, limit::acquire, template,
+        Message, MessageKind, Notifier, NotifierSender,
+    },
+    BoxedFuture,
+};
+
+mod spool;
+
+use spool::{Spool, SpooledEmail};
+
+/// How often spooled emails are retried, besides after successful delivery.
+const SPOOL_REPLAY_INTERVAL: Duration = Duration::from_secs(60);
+/// How often emails in flight are checked on shutdown.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Fail)]
+enum SmtpError {
+    // Delivery failures
+    #[fail(display = "SMTP delivery error: {}", err)]
+    DeliveryError { err: LettreSmtpError },
+    #[fail(display = "Blocking section error: {}", err)]
+    BlockingError { err: BlockingError },
+    #[fail(display = "Timer error: {}", err)]
+    TimerError { err: tokio_timer::Error },
+    #[fail(display = "Parallelism limiter closed: {}", err)]
+    LimiterError { err: AcquireError },
+
+    // Build failures
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserializeError { err: serde_yaml::Error },
+    #[fail(display = "SMTP client error: {}", err)]
+    SmtpClientError { err: lettre::smtp::error::Error },
+    #[fail(display = "TLS error: {}", err)]
+    TlsError { err: native_tls::Error },
+    #[fail(display = "'pwd' is required, when 'login' is set")]
+    MissingPassword,
+    #[fail(display = "'from' is required, when 'login' is not set")]
+    MissingFrom,
+    #[fail(display = "Failed to read template '{}': {}", path, err)]
+    TemplateReadError { path: String, err: io::Error },
+    #[fail(display = "Failed to open spool: {}", err)]
+    SpoolError { err: io::Error },
+    #[fail(display = "'parallelism' must be positive")]
+    InvalidParallelism,
+}
+
+impl From<LettreSmtpError> for SmtpError {
+    fn from(err: LettreSmtpError) -> Self {
+        SmtpError::DeliveryError { err }
+    }
+}
+
+fn read_template(path: Option<PathBuf>) -> Result<Option<String>, SmtpError> {
+    path.map(|path| {
+        fs::read_to_string(&path).map_err(|e| SmtpError::TemplateReadError {
+            path: path.display().to_string(),
+            err: e,
+        })
+    })
+    .transpose()
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct EmailIdent {
+    pub address: String,
+    pub name: Option<String>,
+}
+
+impl EmailIdent {
+    pub(crate) fn new(address: String, name: Option<String>) -> Self {
+        Self { address, name }
+    }
+}
+
+impl From<EmailIdent> for Mailbox {
+    fn from(ident: EmailIdent) -> Self {
+        Mailbox {
+            address: ident.address,
+            name: ident.name,
+        }
+    }
+}
+
+/// Recipient, which gets only messages, that pass its filter.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct Recipient {
+    #[serde(flatten)]
+    pub ident: EmailIdent,
+    #[serde(flatten)]
+    pub filter: MessageFilter,
+}
+
+pub(crate) struct SmtpNotifier {
+    delivery: Arc<SmtpDelivery>,
+    sender: SmtpSender,
+    /// Stops spool replay on shutdown.
+    stop: Mutex<Option<oneshot::Sender<()>>>,
+    stopped: Mutex<Option<oneshot::Receiver<()>>>,
+    shutdown_timeout: Duration,
+}
+
+impl Notifier for SmtpNotifier {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        Box::new(self.sender.clone())
+    }
+
+    fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        let smtp_config = serde_yaml::from_value(config)
+            .map_err(|e| Box::new(SmtpError::YamlDeserializeError { err: e }) as Box<dyn Fail>)?;
+        let SmtpConfig {
+            host,
+            port,
+            tls,
+            login,
+            pwd,
+            mechanism,
+            from,
+            ident,
+            recipients,
+            timeout,
+            text_template,
+            html_template,
+            retries,
+            retry_delay,
+            spool_dir,
+            spool_limit,
+            pool_size,
+            parallelism,
+            shutdown_timeout,
+        } = smtp_config;
+        if parallelism == 0 {
+            return Err(Box::new(SmtpError::InvalidParallelism));
+        }
+        let text_template =
+            read_template(text_template).map_err(|e| Box::new(e) as Box<dyn Fail>)?;
+        let html_template =
+            read_template(html_template).map_err(|e| Box::new(e) as Box<dyn Fail>)?;
+        let from = from
+            .or_else(|| login.clone())
+            .ok_or_else(|| Box::new(SmtpError::MissingFrom) as Box<dyn Fail>)?;
+        let security = match tls {
+            SmtpTls::None => ClientSecurity::None,
+            tls => {
+                let connector = TlsConnector::new()
+                    .map_err(|e| Box::new(SmtpError::TlsError { err: e }) as Box<dyn Fail>)?;
+                let params = ClientTlsParameters::new(host.clone(), connector);
+                match tls {
+                    SmtpTls::Opportunistic => ClientSecurity::Opportunistic(params),
+                    SmtpTls::StartTls => ClientSecurity::Required(params),
+                    _ => ClientSecurity::Wrapper(params),
+                }
+            }
+        };
+        let port = port.unwrap_or_else(|| tls.default_port());
+        let mut client = SmtpClient::new((host.as_str(), port), security)
+            .map_err(|e| Box::new(SmtpError::SmtpClientError { err: e }) as Box<dyn Fail>)?
+            .timeout(Some(Duration::from_millis(timeout)));
+        // Relay without authentication, if there is no login.
+        if let Some(login) = login {
+            let pwd = pwd.ok_or_else(|| Box::new(SmtpError::MissingPassword) as Box<dyn Fail>)?;
+            client = client.credentials(Credentials::new(login, pwd));
+        }
+        if let Some(mechanism) = mechanism {
+            client = client.authentication_mechanism(mechanism.into());
+        }
+        // Pooled connections are kept open between emails.
+        if pool_size > 0 {
+            client = client.connection_reuse(ConnectionReuseParameters::ReuseUnlimited);
+        }
+        let spool = spool_dir
+            .map(|x| Spool::open(x, spool_limit).map(Mutex::new))
+            .transpose()
+            .map_err(|e| Box::new(SmtpError::SpoolError { err: e }) as Box<dyn Fail>)?;
+        let delivery = Arc::new(SmtpDelivery {
+            pool: SmtpPool {
+                client,
+                idle: Mutex::new(Vec::with_capacity(pool_size)),
+                size: pool_size,
+            },
+            semaphore: Arc::new(Semaphore::new(parallelism)),
+            spool,
+            replaying: AtomicBool::new(false),
+            retries,
+            retry_delay: Duration::from_millis(retry_delay),
+            in_flight: AtomicUsize::new(0),
+        });
+        let (stop, stopped) = oneshot::channel();
+        Ok(Box::new(Self {
+            delivery: delivery.clone(),
+            sender: SmtpSender {
+                delivery,
+                recipients,
+                from: EmailIdent::new(from, ident),
+                text_template,
+                html_template,
+            },
+            stop: Mutex::new(Some(stop)),
+            stopped: Mutex::new(Some(stopped)),
+            shutdown_timeout: Duration::from_millis(shutdown_timeout),
+        }))
+    }
+
+    fn background(&self) -> Option<BoxedFuture<(), ()>> {
+        let stopped = self.stopped.lock().unwrap().take()?;
+        let delivery = self.delivery.clone();
+        delivery.spool.as_ref()?;
+        // First tick is immediate, so emails, left from previous run, are delivered on start.
+        let replay = Interval::new(Instant::now(), SPOOL_REPLAY_INTERVAL)
+            .map_err(|e| error!("SmtpNotifier error: {}", SmtpError::TimerError { err: e }))
+            .for_each(move |_| {
+                let delivery = delivery.clone();
+                if delivery.spool_is_empty() {
+                    return Either::A(ok(()));
+                }
+                let replay = move || {
+                    delivery.replay();
+                };
+                Either::B(blocking(replay).map_err(|e| {
+                    error!(
+                        "SmtpNotifier error: {}",
+                        SmtpError::BlockingError { err: e }
+                    )
+                }))
+            });
+        Some(Box::new(replay.select2(stopped).then(|_| {
+            debug!("SMTP spool replay is stopped");
+            Ok(())
+        })))
+    }
+
+    fn shutdown(&self) -> BoxedFuture<(), ()> {
+        let stop = self.stop.lock().unwrap().take();
+        let delivery = self.delivery.clone();
+        let shutdown_timeout = self.shutdown_timeout;
+        Box::new(lazy(move || {
+            if let Some(stop) = stop {
+                let _ = stop.send(());
+            }
+            let deadline = Instant::now() + shutdown_timeout;
+            loop_fn((), move |_| {
+                let in_flight = delivery.in_flight.load(Ordering::SeqCst);
+                if in_flight == 0 {
+                    info!("SMTP notifier is shut down");
+                    Either::A(ok(Loop::Break(())))
+                } else if Instant::now() >= deadline {
+                    warn!(
+                        "SMTP notifier is shut down with {} email(s) in flight",
+                        in_flight
+                    );
+                    Either::A(ok(Loop::Break(())))
+                } else {
+                    debug!("Wait for {} email(s) in flight", in_flight);
+                    Either::B(
+                        Delay::new(Instant::now() + SHUTDOWN_POLL_INTERVAL)
+                            .map(Loop::Continue)
+                            .map_err(|e| {
+                                error!("SmtpNotifier error: {}", SmtpError::TimerError { err: e })
+                            }),
+                    )
+                }
+            })
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SmtpSender {
+    delivery: Arc<SmtpDelivery>,
+    recipients: Vec<Recipient>,
+    from: EmailIdent,
+    text_template: Option<String>,
+    html_template: Option<String>,
+}
+
+impl NotifierSender for SmtpSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let resolved = msg.kind == MessageKind::Resolved;
+        // Digest is filtered part by part, so every recipient gets only its resources.
+        let emails = self
+            .recipients
+            .iter()
+            .filter_map(|x| {
+                let msg = msg.filter(&|m: &Message| x.filter.matches(m))?;
+                let text = match self.text_template {
+                    Some(ref t) => template::render(t, &msg),
+                    None => msg.body.clone(),
+                };
+                let html = self
+                    .html_template
+                    .as_ref()
+                    .map(|t| template::render_html(t, &msg));
+                let builder = lettre_email::Email::builder()
+                    .to(x.ident.clone())
+                    .from(self.from.clone())
+                    .subject(msg.title.clone());
+                let email = match html {
+                    Some(html) => builder.alternative(html, text),
+                    None => builder.text(text),
+                }
+                .build()
+                .unwrap();
+                debug!("Send email with SmtpNotifier: {:#?}", email);
+                Some(SpooledEmail::new(email.into(), resolved).expect("email is built in memory"))
+            })
+            .collect::<Vec<_>>();
+        if emails.is_empty() {
+            debug!("No SMTP recipients for {}", msg.resource_name);
+        }
+        let delivery = self.delivery.clone();
+        // Every email is a separate task, so they are sent concurrently, up to `parallelism` at
+        // once, and are not cancelled, when message future is dropped on shutdown.
+        // Spooled email counts as delivered, as it is delivered by replay.
+        Box::new(lazy(move || {
+            join_deliveries(emails.into_iter().map(move |email| {
+                let (done, done_rx) = oneshot::channel();
+                tokio::spawn(SmtpDelivery::deliver(delivery.clone(), email).then(|res| {
+                    let _ = done.send(res.map_err(|e| delivery_error("SmtpNotifier", e)));
+                    Ok(())
+                }));
+                done_rx.then(|res| res.unwrap_or_else(|e| Err(Box::new(e) as Box<dyn Fail>)))
+            }))
+        }))
+    }
+}
+
+/// Idle SMTP connections, reused by deliveries.
+struct SmtpPool {
+    client: SmtpClient,
+    idle: Mutex<Vec<SmtpTransport>>,
+    /// Maximum number of idle connections.
+    size: usize,
+}
+
+impl SmtpPool {
+    /// Send email with idle or new connection. Blocks current thread.
+    fn send(&self, email: &SpooledEmail) -> SmtpResult {
+        let transport = self.idle.lock().unwrap().pop();
+        let mut transport = transport.unwrap_or_else(|| self.client.clone().transport());
+        let res = transport.send(
+            email
+                .to_sendable()
+                .expect("spooled email has valid envelope"),
+        );
+        if res.is_ok() {
+            let mut idle = self.idle.lock().unwrap();
+            if idle.len() < self.size {
+                idle.push(transport);
+                return res;
+            }
+        }
+        // Broken or excess connection.
+        transport.close();
+        res
+    }
+}
+
+/// Delivery state, shared by notifier and its senders.
+struct SmtpDelivery {
+    pool: SmtpPool,
+    semaphore: Arc<Semaphore>,
+    /// Undelivered emails aren't spooled without it.
+    spool: Option<Mutex<Spool>>,
+    /// Spool replay is running.
+    replaying: AtomicBool,
+    retries: u32,
+    retry_delay: Duration,
+    /// Number of emails, which are not delivered or spooled yet.
+    in_flight: AtomicUsize,
+}
+
+/// Counts email as in flight until dropped.
+struct InFlight(Arc<SmtpDelivery>);
+
+impl InFlight {
+    fn new(delivery: Arc<SmtpDelivery>) -> Self {
+        delivery.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight(delivery)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl SmtpDelivery {
+    fn spool_is_empty(&self) -> bool {
+        match self.spool {
+            Some(ref x) => x.lock().unwrap().is_empty(),
+            None => true,
+        }
+    }
+
+    /// Send email on blocking section of thread pool, when permit is acquired.
+    fn send(
+        delivery: Arc<Self>,
+        email: SpooledEmail,
+    ) -> impl Future<Item = SmtpResult, Error = SmtpError> {
+        acquire(delivery.semaphore.clone())
+            .map_err(|e| SmtpError::LimiterError { err: e })
+            .and_then(move |permit| {
+                blocking(move || {
+                    let res = delivery.pool.send(&email);
+                    drop(permit);
+                    res
+                })
+                .map_err(|e| SmtpError::BlockingError { err: e })
+            })
+    }
+
+    /// Send email, retrying transient failures with exponential backoff. Undelivered email is
+    /// put to spool, if there is one, and counts as delivered then. While spool isn't empty, email is queued behind spooled ones, so it
+    /// doesn't overtake older messages (like new error before its resolution).
+    fn deliver(delivery: Arc<Self>, email: SpooledEmail) -> BoxedFuture<(), SmtpError> {
+        let in_flight = InFlight::new(delivery.clone());
+        let (delivery2, email2) = (delivery.clone(), email.clone());
+        let queue = blocking(move || {
+            let mut spool = match delivery2.spool {
+                Some(ref x) => x.lock().unwrap(),
+                None => return None,
+            };
+            if spool.is_empty() {
+                return None;
+            }
+            debug!("Email {} is queued behind spooled ones", email2.message_id);
+            spool.push(email2.clone());
+            drop(spool);
+            Some(delivery2.replay().contains(&email2.message_id))
+        })
+        .map_err(|e| SmtpError::BlockingError { err: e });
+        let fut = queue
+            .and_then(move |queued| match queued {
+                Some(true) => Either::A(ok(())),
+                Some(false) => {
+                    warn!("Email is spooled behind undelivered ones");
+                    Either::A(ok(()))
+                }
+                None => Either::B(Self::deliver_now(delivery, email)),
+            })
+            .then(move |res| {
+                drop(in_flight);
+                res
+            });
+        Box::new(fut)
+    }
+
+    /// Send email right away with retries, putting it to spool, if it isn't delivered.
+    fn deliver_now(delivery: Arc<Self>, email: SpooledEmail) -> BoxedFuture<(), SmtpError> {
+        let retries = delivery.retries;
+        let retry_delay = delivery.retry_delay;
+        let (delivery2, email2) = (delivery.clone(), email.clone());
+        let fut = loop_fn(0, move |attempt| {
+            Self::send(delivery.clone(), email.clone()).and_then(move |res| match res {
+                Err(e @ LettreSmtpError::Permanent(_)) => Either::A(err(e.into())),
+                Err(e) if attempt < retries => {
+                    let delay = notifier::retry_delay(retry_delay, attempt);
+                    warn!(
+                        "SMTP delivery attempt {} failed ({}), retry in {:?}",
+                        attempt + 1,
+                        e,
+                        delay
+                    );
+                    Either::B(
+                        Delay::new(Instant::now() + delay)
+                            .map(move |_| Loop::Continue(attempt + 1))
+                            .map_err(|e| SmtpError::TimerError { err: e }),
+                    )
+                }
+                Err(e) => Either::A(err(e.into())),
+                Ok(r) => Either::A(ok(Loop::Break(r))),
+            })
+        })
+        .then(move |res| {
+            let delivery = delivery2;
+            blocking(move || {
+                match res {
+                    Ok(ref r) => {
+                        debug!("SmtpNotifier response: {:#?}", r);
+                        delivery.replay();
+                    }
+                    // Server rejects email itself, so it won't be accepted later.
+                    Err(SmtpError::DeliveryError {
+                        err: LettreSmtpError::Permanent(_),
+                    }) => (),
+                    Err(_) => {
+                        if let Some(ref spool) = delivery.spool {
+                            let mut spool = spool.lock().unwrap();
+                            spool.push(email2);
+                            warn!("Email is spooled, {} email(s) in spool", spool.len());
+                            // It is delivered by replay, so there is no fallback.
+                            return Ok(());
+                        }
+                    }
+                }
+                res.map(|_| ())
+            })
+            .map_err(|e| SmtpError::BlockingError { err: e })
+            .and_then(|res| res)
+        });
+        Box::new(fut)
+    }
+
+    /// Send spooled emails, oldest first, until one fails. Returns IDs of delivered emails.
+    /// Only one replay runs at a time, the others return right away, as their emails are
+    /// delivered by it. Spool is locked only between emails. Blocks current thread.
+    fn replay(&self) -> Vec<String> {
+        let mut delivered = Vec::new();
+        let spool = match self.spool {
+            Some(ref x) => x,
+            None => return delivered,
+        };
+        if self.replaying.swap(true, Ordering::SeqCst) {
+            return delivered;
+        }
+        loop {
+            // Email stays in spool, until it is delivered.
+            let email = {
+                let spool = spool.lock().unwrap();
+                match spool.front() {
+                    Some(email) => email.clone(),
+                    None => {
+                        // Cleared under lock, so email, which is queued after that, is replayed
+                        // by its own sender.
+                        self.replaying.store(false, Ordering::SeqCst);
+                        return delivered;
+                    }
+                }
+            };
+            match self.pool.send(&email) {
+                Ok(_) => {
+                    info!("Spooled email {} is delivered", email.message_id);
+                    spool.lock().unwrap().remove(&email.message_id);
+                    delivered.push(email.message_id);
+                }
+                Err(LettreSmtpError::Permanent(r)) => {
+                    error!("Spooled email {} is rejected: {:?}", email.message_id, r);
+                    spool.lock().unwrap().remove(&email.message_id);
+                }
+                Err(e) => {
+                    debug!("Spool replay failed: {}", e);
+                    self.replaying.store(false, Ordering::SeqCst);
+                    return delivered;
+                }
+            }
+        }
+    }
+}
+
+/// How TLS is applied to connection.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub(crate) enum SmtpTls {
+    /// TLS from the start (SMTPS).
+    Implicit,
+    /// Require `STARTTLS`.
+    StartTls,
+    /// Use `STARTTLS`, if server supports it.
+    Opportunistic,
+    /// Plain text connection.
+    None,
+}
+
+impl SmtpTls {
+    fn default_port(self) -> u16 {
+        match self {
+            SmtpTls::Implicit => 465,
+            SmtpTls::StartTls => 587,
+            SmtpTls::Opportunistic | SmtpTls::None => 25,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub(crate) enum SmtpMechanism {
+    Plain,
+    Login,
+    Xoauth2,
+}
+
+impl From<SmtpMechanism> for Mechanism {
+    fn from(mechanism: SmtpMechanism) -> Self {
+        match mechanism {
+            SmtpMechanism::Plain => Mechanism::Plain,
+            SmtpMechanism::Login => Mechanism::Login,
+            SmtpMechanism::Xoauth2 => Mechanism::Xoauth2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct SmtpConfig {
+    pub host: String,
+    /// Default port depends on `tls`.
+    pub port: Option<u16>,
+    #[serde(default = "default_tls")]
+    pub tls: SmtpTls,
+    /// Authentication is skipped without login.
+    pub login: Option<String>,
+    /// Password or, for `Xoauth2` mechanism, access token.
+    pub pwd: Option<String>,
+    /// Forced authentication mechanism, negotiated by default.
+    pub mechanism: Option<SmtpMechanism>,
+    /// Sender address, `login` by default.
+    pub from: Option<String>,
+    pub ident: Option<String>,
+    pub recipients: Vec<Recipient>,
+    /// Network timeout in milliseconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Path to template of plain text part, message body by default.
+    pub text_template: Option<PathBuf>,
+    /// Path to template of HTML part. Email is text only without it.
+    pub html_template: Option<PathBuf>,
+    /// Number of retries of transient failures.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Delay before first retry in milliseconds, doubled on every retry up to 10 minutes.
+    #[serde(default = "default_retry_delay")]
+    pub retry_delay: u64,
+    /// Directory, where undelivered emails are kept until they are delivered, even across
+    /// restarts. It must not be shared with other SMTP notifiers. Emails are not spooled without
+    /// it.
+    pub spool_dir: Option<PathBuf>,
+    /// Maximum number of spooled emails. Resolution notices are never dropped.
+    #[serde(default = "default_spool_limit")]
+    pub spool_limit: usize,
+    /// Maximum number of idle connections, kept open between emails. Zero disables reuse.
+    #[serde(default = "default_pool_size")]
+    pub pool_size: usize,
+    /// Maximum number of emails, sent at once.
+    #[serde(default = "default_parallelism")]
+    pub parallelism: usize,
+    /// How long to wait for emails in flight on shutdown, in milliseconds.
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout: u64,
+}
+
+fn default_tls() -> SmtpTls {
+    SmtpTls::Implicit
+}
+
+fn default_timeout() -> u64 {
+    60_000
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_retry_delay() -> u64 {
+    1000
+}
+
+fn default_spool_limit() -> usize {
+    1000
+}
+
+fn default_pool_size() -> usize {
+    2
+}
+
+fn default_parallelism() -> usize {
+    4
+}
+
+fn default_shutdown_timeout() -> u64 {
+    30_000
+}
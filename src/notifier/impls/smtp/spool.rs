@@ -0,0 +1,221 @@
+//! Dead-letter spool of emails, which were not delivered after all retries.
+
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use chrono::Utc;
+
+use lettre::{EmailAddress, Envelope, SendableEmail};
+
+use log::{error, warn};
+
+use serde::{Deserialize, Serialize};
+
+/// Makes spool file names unique within process.
+static SPOOL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Email in form, which can be sent repeatedly and stored on disk.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct SpooledEmail {
+    pub from: Option<String>,
+    pub to: Vec<String>,
+    pub message_id: String,
+    pub message: String,
+    /// Resolution notices are never evicted from spool.
+    pub resolved: bool,
+}
+
+impl SpooledEmail {
+    pub(crate) fn new(email: SendableEmail, resolved: bool) -> io::Result<Self> {
+        let from = email.envelope().from().map(|x| x.to_string());
+        let to = email
+            .envelope()
+            .to()
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        let message_id = email.message_id().into();
+        Ok(Self {
+            from,
+            to,
+            message_id,
+            message: email.message_to_string()?,
+            resolved,
+        })
+    }
+
+    pub(crate) fn to_sendable(&self) -> Result<SendableEmail, lettre::error::Error> {
+        let from = self.from.clone().map(EmailAddress::new).transpose()?;
+        let to = self
+            .to
+            .iter()
+            .cloned()
+            .map(EmailAddress::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(SendableEmail::new(
+            Envelope::new(from, to)?,
+            self.message_id.clone(),
+            self.message.clone().into_bytes(),
+        ))
+    }
+}
+
+/// Bounded queue of undelivered emails, persisted in directory one file per email.
+pub(crate) struct Spool {
+    dir: PathBuf,
+    limit: usize,
+    entries: VecDeque<(Option<PathBuf>, SpooledEmail)>,
+}
+
+impl Spool {
+    /// Open spool, loading emails left by previous run.
+    pub(crate) fn open(dir: PathBuf, limit: usize) -> io::Result<Self> {
+        let mut entries = VecDeque::new();
+        fs::create_dir_all(&dir)?;
+        let mut paths = fs::read_dir(&dir)?
+            .filter_map(Result::ok)
+            .map(|x| x.path())
+            .filter(|x| x.extension().map(|ext| ext == "json").unwrap_or(false))
+            .collect::<Vec<_>>();
+        // Names start with creation time, so this is oldest first.
+        paths.sort();
+        for path in paths {
+            let email = fs::read(&path).and_then(|x| {
+                serde_json::from_slice::<SpooledEmail>(&x)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            });
+            match email {
+                Ok(email) => match email.to_sendable() {
+                    Ok(_) => entries.push_back((Some(path), email)),
+                    Err(e) => error!("Skip spool file {}: {}", path.display(), e),
+                },
+                Err(e) => error!("Skip broken spool file {}: {}", path.display(), e),
+            }
+        }
+        Ok(Self {
+            dir,
+            limit,
+            entries,
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn front(&self) -> Option<&SpooledEmail> {
+        self.entries.front().map(|(_, email)| email)
+    }
+
+    /// Remove email after its delivery, if it wasn't dropped meanwhile.
+    pub(crate) fn remove(&mut self, message_id: &str) {
+        let i = match self
+            .entries
+            .iter()
+            .position(|(_, x)| x.message_id == message_id)
+        {
+            Some(i) => i,
+            None => return,
+        };
+        if let Some((Some(path), _)) = self.entries.remove(i) {
+            if let Err(e) = fs::remove_file(&path) {
+                error!("Failed to remove spool file {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Add email. If spool is full, oldest email, which is not resolution notice, is dropped.
+    pub(crate) fn push(&mut self, email: SpooledEmail) {
+        if self.entries.len() >= self.limit {
+            match self.entries.iter().position(|(_, x)| !x.resolved) {
+                Some(i) => {
+                    let (path, dropped) = self.entries.remove(i).unwrap();
+                    warn!("Spool is full, drop email {}", dropped.message_id);
+                    if let Some(path) = path {
+                        let _ = fs::remove_file(path);
+                    }
+                }
+                None if !email.resolved => {
+                    warn!("Spool is full, drop email {}", email.message_id);
+                    return;
+                }
+                // Resolution notices are kept even beyond limit.
+                None => (),
+            }
+        }
+        let path = self.dir.join(format!(
+            "{}-{:06}.json",
+            Utc::now().format("%Y%m%dT%H%M%S%.9f"),
+            SPOOL_COUNTER.fetch_add(1, Ordering::Relaxed) % 1_000_000
+        ));
+        let res = serde_json::to_vec(&email)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            .and_then(|x| fs::write(&path, x));
+        let path = match res {
+            Ok(()) => Some(path),
+            // Keep it in memory at least.
+            Err(e) => {
+                error!("Failed to write spool file {}: {}", path.display(), e);
+                None
+            }
+        };
+        self.entries.push_back((path, email));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn email(id: &str, resolved: bool) -> SpooledEmail {
+        SpooledEmail {
+            from: Some("sentinel@example.com".into()),
+            to: vec!["admin@example.com".into()],
+            message_id: id.into(),
+            message: format!("Subject: {}\r\n\r\nBody", id),
+            resolved,
+        }
+    }
+
+    fn ids(spool: &Spool) -> Vec<&str> {
+        spool
+            .entries
+            .iter()
+            .map(|(_, x)| x.message_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn push_never_evicts_resolution_notices() {
+        let dir = env::temp_dir().join(format!("sentinel-spool-test-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut spool = Spool::open(dir.clone(), 2).unwrap();
+        spool.push(email("new-1", false));
+        spool.push(email("resolved-1", true));
+        // Oldest email, which is not resolution notice, is dropped.
+        spool.push(email("new-2", false));
+        assert_eq!(ids(&spool), vec!["resolved-1", "new-2"]);
+        spool.push(email("resolved-2", true));
+        assert_eq!(ids(&spool), vec!["resolved-1", "resolved-2"]);
+        // New email is dropped, when spool is full of resolution notices.
+        spool.push(email("new-3", false));
+        assert_eq!(ids(&spool), vec!["resolved-1", "resolved-2"]);
+        // Resolution notice is kept beyond limit.
+        spool.push(email("resolved-3", true));
+        assert_eq!(ids(&spool), vec!["resolved-1", "resolved-2", "resolved-3"]);
+        // Dropped emails are removed from disk too.
+        let reopened = Spool::open(dir.clone(), 2).unwrap();
+        assert_eq!(ids(&reopened), ids(&spool));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    ffi::CStr,
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    path::PathBuf,
+};
+
+use chrono::SecondsFormat;
+
+use futures::Future;
+use tokio::net::{TcpStream, UdpSocket, UnixDatagram};
+
+use log::debug;
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{delivery_error, Message, MessageKind, Notifier, NotifierSender},
+    BoxedFuture,
+};
+
+#[derive(Debug, Fail)]
+enum SyslogError {
+    // Delivery failures
+    #[fail(display = "IO error: {}", err)]
+    IoError { err: io::Error },
+
+    // Build failures
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserializeError { err: serde_yaml::Error },
+    #[fail(display = "'address' is required for {:?} transport", transport)]
+    MissingAddress { transport: SyslogTransport },
+    #[fail(display = "Invalid address '{}': {}", address, reason)]
+    InvalidAddress { address: String, reason: String },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SyslogTransport {
+    Unix,
+    Udp,
+    Tcp,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Facility {
+    Kern,
+    User,
+    Mail,
+    Daemon,
+    Auth,
+    Syslog,
+    Lpr,
+    News,
+    Uucp,
+    Cron,
+    Authpriv,
+    Ftp,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl Facility {
+    fn code(self) -> u8 {
+        match self {
+            Facility::Kern => 0,
+            Facility::User => 1,
+            Facility::Mail => 2,
+            Facility::Daemon => 3,
+            Facility::Auth => 4,
+            Facility::Syslog => 5,
+            Facility::Lpr => 6,
+            Facility::News => 7,
+            Facility::Uucp => 8,
+            Facility::Cron => 9,
+            Facility::Authpriv => 10,
+            Facility::Ftp => 11,
+            Facility::Local0 => 16,
+            Facility::Local1 => 17,
+            Facility::Local2 => 18,
+            Facility::Local3 => 19,
+            Facility::Local4 => 20,
+            Facility::Local5 => 21,
+            Facility::Local6 => 22,
+            Facility::Local7 => 23,
+        }
+    }
+}
+
+/// Syslog severity of message: err for new and still active, warning for changed and notice for
+/// resolved error.
+pub(crate) fn severity(kind: MessageKind) -> u8 {
+    match kind {
+        MessageKind::New | MessageKind::Reminder => 3,
+        MessageKind::Changed => 4,
+        MessageKind::Resolved => 5,
+    }
+}
+
+/// Host name, or nil value, if it can't be obtained.
+pub(crate) fn hostname() -> String {
+    let mut buf = [0 as libc::c_char; 256];
+    let res = unsafe { libc::gethostname(buf.as_mut_ptr(), buf.len() - 1) };
+    if res != 0 {
+        return "-".into();
+    }
+    let name = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy();
+    if name.is_empty() {
+        "-".into()
+    } else {
+        name.into_owned()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct SyslogConfig {
+    #[serde(default = "default_transport")]
+    transport: SyslogTransport,
+    /// Socket path for unix transport (`/dev/log` by default), `host:port` otherwise.
+    address: Option<String>,
+    #[serde(default = "default_facility")]
+    facility: Facility,
+    #[serde(default = "default_app_name")]
+    app_name: String,
+    /// Override of local host name.
+    hostname: Option<String>,
+    /// SD-ID of structured data element with resource and state.
+    #[serde(default = "default_sd_id")]
+    sd_id: String,
+}
+
+fn default_transport() -> SyslogTransport {
+    SyslogTransport::Unix
+}
+
+fn default_facility() -> Facility {
+    Facility::Daemon
+}
+
+fn default_app_name() -> String {
+    "sentinel".into()
+}
+
+fn default_sd_id() -> String {
+    "sentinel@32473".into()
+}
+
+#[derive(Clone, Debug)]
+enum SyslogAddress {
+    Unix(PathBuf),
+    Udp(SocketAddr),
+    Tcp(SocketAddr),
+}
+
+impl SyslogAddress {
+    fn from_config(config: &SyslogConfig) -> Result<Self, SyslogError> {
+        let address = match (config.transport, config.address.as_ref()) {
+            (SyslogTransport::Unix, None) => return Ok(SyslogAddress::Unix("/dev/log".into())),
+            (SyslogTransport::Unix, Some(x)) => return Ok(SyslogAddress::Unix(x.into())),
+            (transport, None) => return Err(SyslogError::MissingAddress { transport }),
+            (_, Some(x)) => x,
+        };
+        let socket_addr = address
+            .to_socket_addrs()
+            .map_err(|e| SyslogError::InvalidAddress {
+                address: address.clone(),
+                reason: e.to_string(),
+            })?
+            .next()
+            .ok_or_else(|| SyslogError::InvalidAddress {
+                address: address.clone(),
+                reason: "no addresses resolved".into(),
+            })?;
+        Ok(match config.transport {
+            SyslogTransport::Udp => SyslogAddress::Udp(socket_addr),
+            _ => SyslogAddress::Tcp(socket_addr),
+        })
+    }
+}
+
+pub(crate) struct SyslogNotifier {
+    sender: SyslogSender,
+}
+
+impl Notifier for SyslogNotifier {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        Box::new(self.sender.clone())
+    }
+
+    fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        let syslog_config: SyslogConfig = serde_yaml::from_value(config)
+            .map_err(|e| Box::new(SyslogError::YamlDeserializeError { err: e }) as Box<dyn Fail>)?;
+        let address =
+            SyslogAddress::from_config(&syslog_config).map_err(|e| Box::new(e) as Box<dyn Fail>)?;
+        Ok(Box::new(Self {
+            sender: SyslogSender {
+                address,
+                facility: syslog_config.facility,
+                app_name: syslog_config.app_name,
+                hostname: syslog_config.hostname.unwrap_or_else(hostname),
+                sd_id: syslog_config.sd_id,
+            },
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SyslogSender {
+    address: SyslogAddress,
+    facility: Facility,
+    app_name: String,
+    hostname: String,
+    sd_id: String,
+}
+
+/// Escape `"`, `\` and `]` in structured data parameter value.
+fn escape_param(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if let '"' | '\\' | ']' = c {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl SyslogSender {
+    /// RFC 5424 message.
+    fn format(&self, msg: &Message) -> String {
+        format!(
+            "<{}>1 {} {} {} {} {} [{} resource=\"{}\" state=\"{}\"] {}: {}",
+            self.facility.code() * 8 + severity(msg.kind),
+            msg.timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+            self.hostname,
+            self.app_name,
+            std::process::id(),
+            msg.kind.as_str(),
+            self.sd_id,
+            escape_param(&msg.resource_name),
+            msg.kind.as_str(),
+            msg.title,
+            msg.description,
+        )
+    }
+}
+
+impl NotifierSender for SyslogSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let line = self.format(&msg);
+        debug!("Send syslog message: {}", line);
+        let fut: BoxedFuture<(), io::Error> = match self.address {
+            SyslogAddress::Unix(ref path) => {
+                let path = path.clone();
+                Box::new(
+                    futures::future::result(UnixDatagram::unbound())
+                        .and_then(move |socket| socket.send_dgram(line.into_bytes(), path))
+                        .map(|_| ()),
+                )
+            }
+            SyslogAddress::Udp(addr) => {
+                let bind_addr: SocketAddr = if addr.is_ipv4() {
+                    "0.0.0.0:0".parse().unwrap()
+                } else {
+                    "[::]:0".parse().unwrap()
+                };
+                Box::new(
+                    futures::future::result(UdpSocket::bind(&bind_addr))
+                        .and_then(move |socket| socket.send_dgram(line.into_bytes(), &addr))
+                        .map(|_| ()),
+                )
+            }
+            // Octet-counting framing (RFC 6587).
+            SyslogAddress::Tcp(addr) => Box::new(
+                TcpStream::connect(&addr)
+                    .and_then(move |stream| {
+                        tokio::io::write_all(stream, format!("{} {}", line.len(), line))
+                    })
+                    .map(|_| ()),
+            ),
+        };
+        Box::new(fut.map_err(|e| delivery_error("SyslogNotifier", SyslogError::IoError { err: e })))
+    }
+}
@@ -0,0 +1,158 @@
+use reqwest::Url;
+
+use futures::Future;
+
+use log::debug;
+
+use serde::Deserialize;
+use serde_json::json;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{
+        delivery_error,
+        http::{HttpDelivery, HttpDeliveryConfig},
+        Message, MessageKind, Notifier, NotifierSender,
+    },
+    BoxedFuture,
+};
+
+#[derive(Debug, Fail)]
+enum TeamsError {
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserialize { err: serde_yaml::Error },
+    #[fail(display = "Reqwest client error: {}", err)]
+    ReqwestClient { err: reqwest::Error },
+    #[fail(display = "Url parse error: {}", err)]
+    UrlParse { err: reqwest::UrlError },
+}
+
+/// Card format: `MessageCard` for Office 365 connectors, `AdaptiveCard` for Workflows webhooks.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+enum TeamsCard {
+    #[default]
+    MessageCard,
+    AdaptiveCard,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct TeamsConfig {
+    url: String,
+    #[serde(default)]
+    card: TeamsCard,
+    #[serde(flatten)]
+    delivery: HttpDeliveryConfig,
+}
+
+pub(crate) struct TeamsNotifier {
+    sender: TeamsSender,
+}
+
+impl Notifier for TeamsNotifier {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        Box::new(self.sender.clone())
+    }
+
+    fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        let teams_config: TeamsConfig = serde_yaml::from_value(config)
+            .map_err(|e| Box::new(TeamsError::YamlDeserialize { err: e }) as Box<dyn Fail>)?;
+        let url = Url::parse(&teams_config.url)
+            .map_err(|e| Box::new(TeamsError::UrlParse { err: e }) as Box<dyn Fail>)?;
+        let delivery = HttpDelivery::from_config(&teams_config.delivery)
+            .map_err(|e| Box::new(TeamsError::ReqwestClient { err: e }) as Box<dyn Fail>)?;
+        Ok(Box::new(Self {
+            sender: TeamsSender {
+                delivery,
+                url,
+                card: teams_config.card,
+            },
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct TeamsSender {
+    delivery: HttpDelivery,
+    url: Url,
+    card: TeamsCard,
+}
+
+impl TeamsSender {
+    fn payload(&self, msg: &Message) -> serde_json::Value {
+        let facts = [
+            ("Resource", msg.resource_name.clone()),
+            ("State", msg.kind.label().into()),
+            ("Error", msg.description.clone()),
+            ("Time", msg.timestamp.to_rfc3339()),
+        ];
+        match self.card {
+            TeamsCard::MessageCard => json!({
+                "@type": "MessageCard",
+                "@context": "https://schema.org/extensions",
+                "themeColor": format!("{:06x}", msg.kind.color()),
+                "summary": msg.title,
+                "title": msg.title,
+                "sections": [{
+                    "facts": facts
+                        .iter()
+                        .map(|(name, value)| json!({ "name": name, "value": value }))
+                        .collect::<Vec<_>>(),
+                }],
+            }),
+            TeamsCard::AdaptiveCard => {
+                // Adaptive cards support only named colors.
+                let color = match msg.kind {
+                    MessageKind::New | MessageKind::Reminder => "Attention",
+                    MessageKind::Changed => "Warning",
+                    MessageKind::Resolved => "Good",
+                };
+                json!({
+                    "type": "message",
+                    "attachments": [{
+                        "contentType": "application/vnd.microsoft.card.adaptive",
+                        "content": {
+                            "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                            "type": "AdaptiveCard",
+                            "version": "1.4",
+                            "body": [
+                                {
+                                    "type": "TextBlock",
+                                    "text": msg.title,
+                                    "weight": "Bolder",
+                                    "size": "Medium",
+                                    "color": color,
+                                    "wrap": true,
+                                },
+                                {
+                                    "type": "FactSet",
+                                    "facts": facts
+                                        .iter()
+                                        .map(|(title, value)| json!({ "title": title, "value": value }))
+                                        .collect::<Vec<_>>(),
+                                },
+                            ],
+                        },
+                    }],
+                })
+            }
+        }
+    }
+}
+
+impl NotifierSender for TeamsSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let payload = self.payload(&msg);
+        debug!("Send Teams message: {}", payload);
+        let url = self.url.clone();
+        Box::new(
+            self.delivery
+                .send(move |client| client.post(url.clone()).json(&payload))
+                .map(|resp| debug!("TeamsNotifier response: {}", resp.status()))
+                .map_err(|e| delivery_error("TeamsNotifier", e)),
+        )
+    }
+}
@@ -0,0 +1,170 @@
+use reqwest::Url;
+
+use futures::Future;
+
+use log::debug;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{
+        delivery_error,
+        http::{HttpDelivery, HttpDeliveryConfig},
+        join_deliveries, template, Message, Notifier, NotifierSender,
+    },
+    BoxedFuture,
+};
+
+#[derive(Debug, Fail)]
+enum TelegramError {
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserialize { err: serde_yaml::Error },
+    #[fail(display = "Reqwest client error: {}", err)]
+    ReqwestClient { err: reqwest::Error },
+    #[fail(display = "Url parse error: {}", err)]
+    UrlParse { err: reqwest::UrlError },
+}
+
+/// Numeric chat ID or `@channelusername`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum ChatId {
+    Id(i64),
+    Username(String),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+enum ParseMode {
+    MarkdownV2,
+    #[serde(rename = "HTML")]
+    Html,
+}
+
+impl ParseMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            ParseMode::MarkdownV2 => "MarkdownV2",
+            ParseMode::Html => "HTML",
+        }
+    }
+
+    fn escape(self, text: &str) -> String {
+        match self {
+            ParseMode::MarkdownV2 => {
+                let mut escaped = String::with_capacity(text.len());
+                for c in text.chars() {
+                    if "_*[]()~`>#+-=|{}.!\\".contains(c) {
+                        escaped.push('\\');
+                    }
+                    escaped.push(c);
+                }
+                escaped
+            }
+            ParseMode::Html => template::escape_html(text),
+        }
+    }
+
+    fn format(self, msg: &Message) -> String {
+        match self {
+            ParseMode::MarkdownV2 => {
+                format!("*{}*\n{}", self.escape(&msg.title), self.escape(&msg.body))
+            }
+            ParseMode::Html => format!(
+                "<b>{}</b>\n{}",
+                self.escape(&msg.title),
+                self.escape(&msg.body)
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct TelegramConfig {
+    token: String,
+    chat_ids: Vec<ChatId>,
+    #[serde(default = "default_parse_mode")]
+    parse_mode: ParseMode,
+    /// Bot API base URL.
+    #[serde(default = "default_api_url")]
+    api_url: String,
+    #[serde(flatten)]
+    delivery: HttpDeliveryConfig,
+}
+
+fn default_parse_mode() -> ParseMode {
+    ParseMode::Html
+}
+
+fn default_api_url() -> String {
+    "https://api.telegram.org".into()
+}
+
+pub(crate) struct TelegramNotifier {
+    sender: TelegramSender,
+}
+
+impl Notifier for TelegramNotifier {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        Box::new(self.sender.clone())
+    }
+
+    fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        let telegram_config: TelegramConfig = serde_yaml::from_value(config)
+            .map_err(|e| Box::new(TelegramError::YamlDeserialize { err: e }) as Box<dyn Fail>)?;
+        let url = Url::parse(&format!(
+            "{}/bot{}/sendMessage",
+            telegram_config.api_url.trim_end_matches('/'),
+            telegram_config.token
+        ))
+        .map_err(|e| Box::new(TelegramError::UrlParse { err: e }) as Box<dyn Fail>)?;
+        let delivery = HttpDelivery::from_config(&telegram_config.delivery)
+            .map_err(|e| Box::new(TelegramError::ReqwestClient { err: e }) as Box<dyn Fail>)?;
+        Ok(Box::new(Self {
+            sender: TelegramSender {
+                delivery,
+                url,
+                chat_ids: telegram_config.chat_ids,
+                parse_mode: telegram_config.parse_mode,
+            },
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct TelegramSender {
+    delivery: HttpDelivery,
+    /// `sendMessage` URL, which contains bot token.
+    url: Url,
+    chat_ids: Vec<ChatId>,
+    parse_mode: ParseMode,
+}
+
+impl NotifierSender for TelegramSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let text = self.parse_mode.format(&msg);
+        let sends = self
+            .chat_ids
+            .iter()
+            .map(|chat_id| {
+                let payload = json!({
+                    "chat_id": chat_id,
+                    "text": text,
+                    "parse_mode": self.parse_mode.as_str(),
+                });
+                debug!("Send Telegram message: {}", payload);
+                let url = self.url.clone();
+                self.delivery
+                    .send(move |client| client.post(url.clone()).json(&payload))
+                    .map(|resp| debug!("TelegramNotifier response: {}", resp.status()))
+                    .map_err(|e| delivery_error("TelegramNotifier", e))
+            })
+            .collect::<Vec<_>>();
+        join_deliveries(sends)
+    }
+}
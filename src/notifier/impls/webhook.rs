@@ -0,0 +1,179 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Url,
+};
+
+use futures::Future;
+
+use log::debug;
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{
+        delivery_error,
+        http::{HttpDelivery, HttpDeliveryConfig},
+        template, Message, Notifier, NotifierSender,
+    },
+    BoxedFuture,
+};
+
+#[derive(Debug, Fail)]
+enum WebhookError {
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserializeError { err: serde_yaml::Error },
+    #[fail(display = "Reqwest client error: {}", err)]
+    ReqwestClientError { err: reqwest::Error },
+    #[fail(display = "Url parse error: {}", err)]
+    UrlParseError { err: reqwest::UrlError },
+    #[fail(display = "Invalid header '{}'", name)]
+    InvalidHeader { name: String },
+    #[fail(display = "Only one of 'payload' and 'form' can be set")]
+    AmbiguousBody,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct WebhookConfig {
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// Sent as `Authorization: Bearer <auth_token>`.
+    auth_token: Option<String>,
+    /// JSON body template.
+    payload: Option<serde_json::Value>,
+    /// Form body template.
+    form: Option<BTreeMap<String, String>>,
+    #[serde(flatten)]
+    delivery: HttpDeliveryConfig,
+}
+
+#[derive(Clone, Debug)]
+enum WebhookBody {
+    Json(serde_json::Value),
+    Form(BTreeMap<String, String>),
+}
+
+impl WebhookBody {
+    fn default_payload() -> Self {
+        let fields = [
+            "title",
+            "body",
+            "resource",
+            "state",
+            "description",
+            "since",
+            "timestamp",
+        ];
+        WebhookBody::Json(serde_json::Value::Object(
+            fields
+                .iter()
+                .map(|x| (x.to_string(), format!("{{{{{}}}}}", x).into()))
+                .collect(),
+        ))
+    }
+
+    fn render(&self, msg: &Message) -> Self {
+        match self {
+            WebhookBody::Json(x) => WebhookBody::Json(template::render_json(x, msg)),
+            WebhookBody::Form(x) => WebhookBody::Form(
+                x.iter()
+                    .map(|(k, v)| (k.clone(), template::render(v, msg)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+pub(crate) struct WebhookNotifier {
+    sender: WebhookSender,
+}
+
+impl Notifier for WebhookNotifier {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        Box::new(self.sender.clone())
+    }
+
+    fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        let webhook_config: WebhookConfig = serde_yaml::from_value(config).map_err(|e| {
+            Box::new(WebhookError::YamlDeserializeError { err: e }) as Box<dyn Fail>
+        })?;
+        let url = Url::parse(&webhook_config.url)
+            .map_err(|e| Box::new(WebhookError::UrlParseError { err: e }) as Box<dyn Fail>)?;
+        let headers = webhook_config
+            .headers
+            .iter()
+            .map(
+                |(name, value)| match (HeaderName::from_str(name), HeaderValue::from_str(value)) {
+                    (Ok(name), Ok(value)) => Ok((name, value)),
+                    _ => Err(WebhookError::InvalidHeader { name: name.clone() }),
+                },
+            )
+            .collect::<Result<HeaderMap, _>>()
+            .map_err(|e| Box::new(e) as Box<dyn Fail>)?;
+        let body = match (webhook_config.payload, webhook_config.form) {
+            (Some(_), Some(_)) => return Err(Box::new(WebhookError::AmbiguousBody)),
+            (Some(payload), None) => WebhookBody::Json(payload),
+            (None, Some(form)) => WebhookBody::Form(form),
+            (None, None) => WebhookBody::default_payload(),
+        };
+        let delivery = HttpDelivery::from_config(&webhook_config.delivery)
+            .map_err(|e| Box::new(WebhookError::ReqwestClientError { err: e }) as Box<dyn Fail>)?;
+        Ok(Box::new(Self {
+            sender: WebhookSender {
+                delivery,
+                url,
+                headers,
+                auth_token: webhook_config.auth_token,
+                body,
+            },
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct WebhookSender {
+    delivery: HttpDelivery,
+    url: Url,
+    headers: HeaderMap,
+    auth_token: Option<String>,
+    body: WebhookBody,
+}
+
+impl NotifierSender for WebhookSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let body = self.body.render(&msg);
+        // Path and query of URL may contain secret token.
+        debug!(
+            "Send webhook to {}: {:?}",
+            self.url.host_str().unwrap_or_default(),
+            body
+        );
+        let (url, headers, auth_token) = (
+            self.url.clone(),
+            self.headers.clone(),
+            self.auth_token.clone(),
+        );
+        Box::new(
+            self.delivery
+                .send(move |client| {
+                    let mut request = client.post(url.clone()).headers(headers.clone());
+                    if let Some(ref token) = auth_token {
+                        request = request.bearer_auth(token);
+                    }
+                    match body {
+                        WebhookBody::Json(ref x) => request.json(x),
+                        WebhookBody::Form(ref x) => request.form(x),
+                    }
+                })
+                .map(|resp| debug!("WebhookNotifier response: {}", resp.status()))
+                .map_err(|e| delivery_error("WebhookNotifier", e)),
+        )
+    }
+}
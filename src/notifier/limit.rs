@@ -0,0 +1,35 @@
+//! Concurrency limit of deliveries.
+
+use std::sync::Arc;
+
+use futures::{future::poll_fn, try_ready, Async, Future};
+use tokio_sync::semaphore::{AcquireError, Permit, Semaphore};
+
+/// Acquired semaphore permit, released on drop.
+pub(crate) struct SemaphorePermit {
+    permit: Permit,
+    semaphore: Arc<Semaphore>,
+}
+
+impl Drop for SemaphorePermit {
+    fn drop(&mut self) {
+        self.permit.release(&self.semaphore);
+    }
+}
+
+/// Wait for free permit of semaphore.
+pub(crate) fn acquire(
+    semaphore: Arc<Semaphore>,
+) -> impl Future<Item = SemaphorePermit, Error = AcquireError> {
+    let mut permit = Some(SemaphorePermit {
+        permit: Permit::new(),
+        semaphore,
+    });
+    poll_fn(move || {
+        {
+            let p = permit.as_mut().expect("polled after completion");
+            try_ready!(p.permit.poll_acquire(&p.semaphore));
+        }
+        Ok(Async::Ready(permit.take().unwrap()))
+    })
+}
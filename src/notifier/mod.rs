@@ -1,31 +1,302 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use chrono::{DateTime, Utc};
+use futures::{future::join_all, Future};
+
+use log::error;
+
 use serde::Deserialize;
 
 use failure::Fail;
 
 use crate::BoxedFuture;
 
+mod batch;
+mod fallback;
+mod filter;
+mod http;
 mod impls;
+mod limit;
+mod rate_limit;
+mod reminder;
+mod template;
+mod wrapper;
 
+pub(crate) use batch::BatchNotifier;
+pub(crate) use fallback::FallbackNotifier;
 pub(crate) use impls::*;
+pub(crate) use rate_limit::{RateLimitConfig, RateLimitNotifier, TokenBucket};
+pub(crate) use reminder::{ReminderConfig, ReminderNotifier};
+
+/// Longest delay between delivery retries.
+pub(crate) const MAX_RETRY_DELAY: Duration = Duration::from_secs(600);
 
 #[derive(Debug, Clone, Deserialize)]
 pub(crate) struct YamlConfig {
     pub name: String,
     #[serde(rename = "type")]
     pub type_: String,
+    /// Names of notifiers, which are tried in order, when this one fails to deliver message.
+    #[serde(default)]
+    pub fallback: Vec<String>,
+    /// Limit of messages (or digests), sent by notifier.
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Send messages, which arrive close together, as digest.
+    pub batch: Option<batch::BatchConfig>,
+    /// Repeat messages about errors, which stay active.
+    #[serde(flatten)]
+    pub reminder: ReminderConfig,
     pub config: serde_yaml::Value,
 }
 
+/// What happened to resource error, ordered from the most alarming.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub(crate) enum MessageKind {
+    New,
+    Changed,
+    /// Error is still active.
+    Reminder,
+    Resolved,
+}
+
+impl MessageKind {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            MessageKind::New => "new",
+            MessageKind::Changed => "changed",
+            MessageKind::Reminder => "reminder",
+            MessageKind::Resolved => "resolved",
+        }
+    }
+
+    /// Capitalized name for message fields.
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            MessageKind::New => "New",
+            MessageKind::Changed => "Changed",
+            MessageKind::Reminder => "Reminder",
+            MessageKind::Resolved => "Resolved",
+        }
+    }
+
+    /// RGB color for chat messages: red for new and reminder, orange for changed and green for
+    /// resolved.
+    pub(crate) fn color(self) -> u32 {
+        match self {
+            MessageKind::New | MessageKind::Reminder => 0xd0_00_00,
+            MessageKind::Changed => 0xff_8c_00,
+            MessageKind::Resolved => 0x2e_b8_86,
+        }
+    }
+}
+
+/// How important resource is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub(crate) enum Severity {
+    Info,
+    Warning,
+    #[default]
+    Error,
+    Critical,
+}
+
+impl Severity {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+/// Description of resource from its config, attached to every message.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct ResourceInfo {
+    /// Link to runbook of resource.
+    pub runbook: Option<String>,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// Severity, set in resource config. Notifiers may use their own default without it.
+    #[serde(default)]
+    pub severity: Option<Severity>,
+}
+
+impl ResourceInfo {
+    pub(crate) fn severity(&self) -> Severity {
+        self.severity.unwrap_or_default()
+    }
+}
+
+/// Result of single resource check.
+#[derive(Clone, Debug)]
+pub(crate) struct CheckResult {
+    pub timestamp: DateTime<Utc>,
+    /// Error description, `None` for successful check.
+    pub error: Option<String>,
+}
+
+/// Messages, combined into single one by wrapper (like digest of batch).
+#[derive(Clone)]
+struct Combined {
+    parts: Vec<Message>,
+    /// Builds combined message of parts.
+    combine: fn(&[Message]) -> Message,
+}
+
 #[derive(Clone)]
 pub(crate) struct Message {
     title: String,
     body: String,
+    kind: MessageKind,
+    /// Resource name, or list of resources of combined message, which is only for display.
+    resource_name: String,
+    /// Description of current error (resolved one for `MessageKind::Resolved`).
+    description: String,
+    /// When the error was first observed.
+    since: DateTime<Utc>,
+    timestamp: DateTime<Utc>,
+    /// Recent check results, oldest first.
+    history: Vec<CheckResult>,
+    info: ResourceInfo,
+    combined: Option<Combined>,
 }
 
 impl Message {
-    pub(crate) fn new(title: String, body: String) -> Self {
-        Self { title, body }
+    pub(crate) fn new(
+        title: String,
+        body: String,
+        kind: MessageKind,
+        resource_name: String,
+        description: String,
+        since: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            title,
+            body,
+            kind,
+            resource_name,
+            description,
+            since,
+            timestamp: Utc::now(),
+            history: Vec::new(),
+            info: ResourceInfo::default(),
+            combined: None,
+        }
+    }
+
+    /// Single message of `parts`, built by `combine`, which can be split back by notifiers,
+    /// that track state of resources, and filtered part by part.
+    pub(crate) fn combine(parts: Vec<Message>, combine: fn(&[Message]) -> Message) -> Self {
+        let msg = combine(&parts);
+        Self {
+            combined: Some(Combined { parts, combine }),
+            ..msg
+        }
+    }
+
+    /// The last message about each resource, in order of their arrival. Message, which isn't
+    /// combined, is the only one.
+    pub(crate) fn per_resource(&self) -> Vec<Message> {
+        let mut messages = Vec::<Message>::new();
+        for msg in self.leaves() {
+            messages.retain(|x| x.resource_name != msg.resource_name);
+            messages.push(msg.clone());
+        }
+        messages
     }
+
+    /// Messages, which aren't combined, in order of their arrival.
+    fn leaves(&self) -> Vec<&Message> {
+        match self.combined {
+            Some(ref combined) => combined.parts.iter().flat_map(Message::leaves).collect(),
+            None => vec![self],
+        }
+    }
+
+    /// Message with parts, which match `filter`, if there are any.
+    pub(crate) fn filter<F: Fn(&Message) -> bool>(&self, filter: &F) -> Option<Message> {
+        let combined = match self.combined {
+            Some(ref combined) => combined,
+            None => {
+                return if filter(self) {
+                    Some(self.clone())
+                } else {
+                    None
+                }
+            }
+        };
+        let parts = combined
+            .parts
+            .iter()
+            .filter_map(|x| x.filter(filter))
+            .collect::<Vec<_>>();
+        if parts.is_empty() {
+            None
+        } else {
+            Some(Message::combine(parts, combined.combine))
+        }
+    }
+
+    /// Attach recent check results and description of resource.
+    pub(crate) fn with_context(mut self, history: Vec<CheckResult>, info: ResourceInfo) -> Self {
+        self.history = history;
+        self.info = info;
+        self
+    }
+
+    /// Reminder about the same error, which is still active.
+    pub(crate) fn reminder(&self) -> Self {
+        let now = Utc::now();
+        Self {
+            title: format!("Error (still failing) {}", self.resource_name),
+            body: format!(
+                "Resource {} still failing since {} ({}):\n{}",
+                self.resource_name,
+                self.since.to_rfc3339(),
+                format_duration(now - self.since),
+                self.description
+            ),
+            kind: MessageKind::Reminder,
+            timestamp: now,
+            ..self.clone()
+        }
+    }
+
+    /// All fields as flat JSON object.
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "title": self.title,
+            "body": self.body,
+            "resource": self.resource_name,
+            "state": self.kind.as_str(),
+            "severity": self.info.severity().as_str(),
+            "labels": self.info.labels,
+            "description": self.description,
+            "since": self.since.to_rfc3339(),
+            "timestamp": self.timestamp.to_rfc3339(),
+        })
+    }
+}
+
+/// Human readable duration, like `1d 2h 5m`.
+fn format_duration(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes();
+    if minutes < 1 {
+        return format!("{}s", duration.num_seconds().max(0));
+    }
+    let parts = [
+        (minutes / 1440, "d"),
+        (minutes / 60 % 24, "h"),
+        (minutes % 60, "m"),
+    ];
+    parts
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, unit)| format!("{}{}", n, unit))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 pub(crate) trait Notifier {
@@ -33,8 +304,92 @@ pub(crate) trait Notifier {
     fn from_config(config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
     where
         Self: Sized;
+
+    /// Background work (like retries of failed deliveries), which runs until shutdown.
+    fn background(&self) -> Option<BoxedFuture<(), ()>> {
+        None
+    }
+
+    /// Stop background work and wait for pending deliveries. Returned future does nothing
+    /// until polled.
+    fn shutdown(&self) -> BoxedFuture<(), ()> {
+        Box::new(futures::future::ok(()))
+    }
 }
 
 pub(crate) trait NotifierSender: Send {
-    fn send_message(&self, msg: Message) -> BoxedFuture<(), ()>;
+    /// Deliver message. Errors are logged by notifier and passed to caller, which may fall back
+    /// to another notifier.
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>>;
+}
+
+/// Log delivery error of notifier and pass it on.
+pub(crate) fn delivery_error<E: Fail>(notifier: &str, err: E) -> Box<dyn Fail> {
+    error!("{} error: {}", notifier, err);
+    Box::new(err)
+}
+
+/// Wait for deliveries to all targets of message, failing with the first error, if any of them
+/// fails.
+pub(crate) fn join_deliveries<I>(deliveries: I) -> BoxedFuture<(), Box<dyn Fail>>
+where
+    I: IntoIterator,
+    I::Item: Future<Item = (), Error = Box<dyn Fail>> + Send + 'static,
+{
+    let deliveries = deliveries
+        .into_iter()
+        .map(|x| x.then(Ok::<_, ()>))
+        .collect::<Vec<_>>();
+    Box::new(
+        join_all(deliveries)
+            .map_err(|_| unreachable!())
+            .and_then(|results| results.into_iter().collect::<Result<Vec<_>, _>>())
+            .map(|_| ()),
+    )
+}
+
+/// Delay before retry after failed `attempt` (counted from zero), which starts with `first` and
+/// is doubled on every retry, up to `MAX_RETRY_DELAY`.
+pub(crate) fn retry_delay(first: Duration, attempt: u32) -> Duration {
+    2u32.checked_pow(attempt)
+        .and_then(|x| first.checked_mul(x))
+        .map_or(MAX_RETRY_DELAY, |x| x.min(MAX_RETRY_DELAY))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles() {
+        let first = Duration::from_millis(100);
+        assert_eq!(retry_delay(first, 0), first);
+        assert_eq!(retry_delay(first, 3), Duration::from_millis(800));
+    }
+
+    #[test]
+    fn retry_delay_is_capped() {
+        let first = Duration::from_secs(1);
+        assert_eq!(retry_delay(first, 20), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(first, 32), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(first, u32::MAX), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(Duration::MAX, 1), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn format_duration_below_minute_in_seconds() {
+        assert_eq!(format_duration(chrono::Duration::seconds(0)), "0s");
+        assert_eq!(format_duration(chrono::Duration::seconds(59)), "59s");
+        assert_eq!(format_duration(chrono::Duration::seconds(-5)), "0s");
+    }
+
+    #[test]
+    fn format_duration_skips_empty_units() {
+        assert_eq!(format_duration(chrono::Duration::seconds(90)), "1m");
+        assert_eq!(format_duration(chrono::Duration::minutes(65)), "1h 5m");
+        assert_eq!(format_duration(chrono::Duration::hours(48)), "2d");
+        let duration =
+            chrono::Duration::days(1) + chrono::Duration::hours(2) + chrono::Duration::minutes(5);
+        assert_eq!(format_duration(duration), "1d 2h 5m");
+    }
 }
@@ -0,0 +1,392 @@
+//! Rate limits of notifiers, which protect recipients from floods of messages.
+//!
+//! Limits are token buckets, refilled continuously. Messages, which exceed limit, and the following
+//! ones are counted and summarised into single message, when limit window ends.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{
+        wrapper::{Window, Wrapped, Wrapper},
+        Message, Notifier, NotifierSender, ResourceInfo,
+    },
+    BoxedFuture,
+};
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct RateLimitConfig {
+    /// Maximum number of messages within `per`.
+    max: u32,
+    /// Limit window in milliseconds.
+    per: u64,
+}
+
+/// Token bucket, refilled by `max` tokens within `per`.
+pub(crate) struct TokenBucket {
+    max: f64,
+    per: Duration,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            max: f64::from(config.max),
+            per: Duration::from_millis(config.per),
+            state: Mutex::new(BucketState {
+                tokens: f64::from(config.max),
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    fn take(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let refill = (now - state.updated).as_secs_f64() / self.per.as_secs_f64() * self.max;
+        state.tokens = (state.tokens + refill).min(self.max);
+        state.updated = now;
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Return token, which wasn't used.
+    fn put_back(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.tokens = (state.tokens + 1.0).min(self.max);
+    }
+}
+
+/// Wrapper, which limits rate of messages of inner notifier by its own and global limits.
+pub(crate) type RateLimitNotifier = Wrapper<Limiter>;
+
+impl RateLimitNotifier {
+    pub(crate) fn wrap(
+        inner: Box<dyn Notifier>,
+        config: Option<RateLimitConfig>,
+        global: Option<Arc<TokenBucket>>,
+    ) -> Box<dyn Notifier> {
+        let buckets = config
+            .map(|x| Arc::new(TokenBucket::new(&x)))
+            .into_iter()
+            .chain(global)
+            .collect::<Vec<_>>();
+        if buckets.is_empty() {
+            return inner;
+        }
+        let limiter = Limiter {
+            buckets,
+            state: Mutex::new(LimiterState {
+                sender: inner.sender(),
+                suppressed: Vec::new(),
+                window: Window::default(),
+            }),
+        };
+        Wrapper::boxed(inner, limiter)
+    }
+}
+
+pub(crate) struct Limiter {
+    /// Own and global buckets, message must get token from each of them.
+    buckets: Vec<Arc<TokenBucket>>,
+    state: Mutex<LimiterState>,
+}
+
+impl Limiter {
+    /// Take token from every bucket. Returns window of exceeded limit otherwise.
+    fn acquire(&self) -> Result<(), Duration> {
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            if !bucket.take() {
+                self.buckets[..i].iter().for_each(|x| x.put_back());
+                return Err(bucket.per);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Wrapped for Limiter {
+    fn sender(self: Arc<Self>) -> Box<dyn NotifierSender> {
+        Box::new(RateLimitSender { limiter: self })
+    }
+
+    fn close(&self) -> BoxedFuture<(), Box<dyn Fail>> {
+        let mut state = self.state.lock().unwrap();
+        state.window.close();
+        state.flush()
+    }
+}
+
+struct LimiterState {
+    sender: Box<dyn NotifierSender>,
+    suppressed: Vec<Message>,
+    /// Window of exceeded limit, summary is sent, when it ends.
+    window: Window,
+}
+
+impl LimiterState {
+    /// Send summary of suppressed messages.
+    fn flush(&mut self) -> BoxedFuture<(), Box<dyn Fail>> {
+        if self.suppressed.is_empty() {
+            return Box::new(futures::future::ok(()));
+        }
+        // Notifiers, which track state of resources, get the last message about each of them.
+        let msg = Message::combine(self.suppressed.drain(..).collect(), summary);
+        self.sender.send_message(msg)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RateLimitSender {
+    limiter: Arc<Limiter>,
+}
+
+impl NotifierSender for RateLimitSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let mut state = self.limiter.state.lock().unwrap();
+        if state.window.is_closed() {
+            return state.sender.send_message(msg);
+        }
+        // Messages, which follow suppressed ones, are suppressed too, until window ends. So states
+        // of resource are delivered in order.
+        if !state.window.is_open() {
+            let window = match self.limiter.acquire() {
+                Ok(()) => return state.sender.send_message(msg),
+                Err(window) => window,
+            };
+            warn!(
+                "Rate limit is exceeded, messages are suppressed for {:?}",
+                window
+            );
+            let limiter = self.limiter.clone();
+            state.window.open(window, move || {
+                let mut state = limiter.state.lock().unwrap();
+                state.window.ended();
+                state.flush()
+            });
+        }
+        debug!("Message about {} is suppressed", msg.resource_name);
+        state.suppressed.push(msg);
+        // Suppression isn't delivery failure, so there is no fallback.
+        Box::new(futures::future::ok(()))
+    }
+}
+
+/// Single message with number of suppressed messages and the last state of each resource.
+fn summary(messages: &[Message]) -> Message {
+    let mut resources = BTreeMap::new();
+    for msg in messages {
+        let entry = resources
+            .entry(msg.resource_name.as_str())
+            .or_insert((0, msg.kind));
+        entry.0 += 1;
+        entry.1 = msg.kind;
+    }
+    let title = format!("{} notifications suppressed", messages.len());
+    let body = resources
+        .iter()
+        .map(|(resource, (n, kind))| format!("  {}: {}, last is {}", resource, n, kind.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    // The most alarming of the last states.
+    let kind = resources.values().map(|x| x.1).min().unwrap();
+    let info = ResourceInfo {
+        severity: messages.iter().map(|x| x.info.severity).max().unwrap(),
+        ..ResourceInfo::default()
+    };
+    Message::new(
+        title.clone(),
+        format!("{} because of rate limit:\n{}", title, body),
+        kind,
+        resources.keys().cloned().collect::<Vec<_>>().join(", "),
+        title,
+        messages.iter().map(|x| x.since).min().unwrap(),
+    )
+    .with_context(Vec::new(), info)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use futures::Future;
+
+    use super::*;
+    use crate::notifier::{MessageKind, Severity};
+
+    fn bucket(max: u32) -> TokenBucket {
+        TokenBucket::new(&RateLimitConfig { max, per: 60_000 })
+    }
+
+    fn message(resource: &str, kind: MessageKind, severity: Severity) -> Message {
+        Message::new(
+            format!("{} {}", kind.label(), resource),
+            String::new(),
+            kind,
+            resource.into(),
+            String::new(),
+            Utc::now(),
+        )
+        .with_context(
+            Vec::new(),
+            ResourceInfo {
+                severity: Some(severity),
+                ..ResourceInfo::default()
+            },
+        )
+    }
+
+    #[test]
+    fn bucket_takes_up_to_max() {
+        let bucket = bucket(2);
+        assert!(bucket.take());
+        assert!(bucket.take());
+        assert!(!bucket.take());
+        bucket.put_back();
+        assert!(bucket.take());
+        assert!(!bucket.take());
+    }
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let bucket = bucket(2);
+        assert!(bucket.take());
+        assert!(bucket.take());
+        // Half of window refills one token.
+        bucket.state.lock().unwrap().updated -= Duration::from_secs(30);
+        assert!(bucket.take());
+        assert!(!bucket.take());
+        // Bucket doesn't overflow.
+        bucket.state.lock().unwrap().updated -= Duration::from_secs(600);
+        assert!(bucket.take());
+        assert!(bucket.take());
+        assert!(!bucket.take());
+    }
+
+    #[test]
+    fn limiter_returns_tokens_of_passed_buckets() {
+        let own = Arc::new(bucket(1));
+        let global = Arc::new(bucket(1));
+        assert!(global.take());
+        let limiter = Limiter {
+            buckets: vec![own.clone(), global],
+            state: Mutex::new(LimiterState {
+                sender: Box::new(NullSender),
+                suppressed: Vec::new(),
+                window: Window::default(),
+            }),
+        };
+        assert_eq!(limiter.acquire(), Err(Duration::from_secs(60)));
+        assert!(own.take());
+    }
+
+    struct NullSender;
+
+    impl NotifierSender for NullSender {
+        fn send_message(&self, _msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+            Box::new(futures::future::ok(()))
+        }
+    }
+
+    /// Sender, which records delivered messages.
+    #[derive(Clone, Default)]
+    struct RecordSender(Arc<Mutex<Vec<Message>>>);
+
+    impl NotifierSender for RecordSender {
+        fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+            self.0.lock().unwrap().push(msg);
+            Box::new(futures::future::ok(()))
+        }
+    }
+
+    #[test]
+    fn limiter_suppresses_messages_until_window_ends() {
+        let delivered = RecordSender::default();
+        let limiter = Arc::new(Limiter {
+            buckets: vec![Arc::new(bucket(1))],
+            state: Mutex::new(LimiterState {
+                sender: Box::new(delivered.clone()),
+                suppressed: Vec::new(),
+                window: Window::default(),
+            }),
+        });
+        let sender = limiter.clone().sender();
+        let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+        runtime
+            .block_on(futures::future::lazy(|| {
+                sender
+                    .send_message(message("web-2", MessageKind::New, Severity::Warning))
+                    .wait()
+                    .unwrap();
+                sender
+                    .send_message(message("db-1", MessageKind::New, Severity::Warning))
+                    .wait()
+                    .unwrap();
+                // Refilled token doesn't let message overtake suppressed ones.
+                limiter.buckets[0].state.lock().unwrap().updated -= Duration::from_secs(60);
+                sender
+                    .send_message(message("db-1", MessageKind::Resolved, Severity::Warning))
+                    .wait()
+                    .unwrap();
+                limiter.close()
+            }))
+            .unwrap();
+        let delivered = delivered.0.lock().unwrap();
+        assert_eq!(delivered.len(), 2);
+        assert_eq!(delivered[0].resource_name, "web-2");
+        let parts = delivered[1]
+            .per_resource()
+            .iter()
+            .map(|x| (x.resource_name.clone(), x.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(parts, vec![("db-1".to_string(), MessageKind::Resolved)]);
+    }
+
+    #[test]
+    fn summary_counts_by_resource() {
+        let messages = vec![
+            message("db-1", MessageKind::New, Severity::Warning),
+            message("web-2", MessageKind::New, Severity::Critical),
+            message("db-1", MessageKind::Resolved, Severity::Warning),
+        ];
+        let msg = Message::combine(messages, summary);
+        assert_eq!(msg.title, "3 notifications suppressed");
+        assert_eq!(
+            msg.body,
+            "3 notifications suppressed because of rate limit:\n  \
+             db-1: 2, last is resolved\n  web-2: 1, last is new"
+        );
+        assert_eq!(msg.kind, MessageKind::New);
+        assert_eq!(msg.info.severity, Some(Severity::Critical));
+        let parts = msg
+            .per_resource()
+            .iter()
+            .map(|x| (x.resource_name.clone(), x.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            parts,
+            vec![
+                ("web-2".to_string(), MessageKind::New),
+                ("db-1".to_string(), MessageKind::Resolved),
+            ]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Repeated notifications about errors, which stay active.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::{sync::oneshot, Future, Stream};
+use tokio_timer::Interval;
+
+use log::{debug, error};
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{
+        wrapper::{Wrapped, Wrapper},
+        Message, MessageKind, Notifier, NotifierSender,
+    },
+    BoxedFuture,
+};
+
+/// Reminders are disabled without `remind_every`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct ReminderConfig {
+    /// Interval between reminders, in milliseconds.
+    pub remind_every: Option<u64>,
+    /// Maximum number of reminders about single error, unlimited by default.
+    pub max_reminders: Option<u32>,
+}
+
+/// Wrapper, which repeats last message about active error of each resource until it is
+/// resolved.
+pub(crate) type ReminderNotifier = Wrapper<Reminders>;
+
+impl ReminderNotifier {
+    pub(crate) fn wrap(inner: Box<dyn Notifier>, config: ReminderConfig) -> Box<dyn Notifier> {
+        let every = match config.remind_every {
+            Some(x) => Duration::from_millis(x),
+            None => return inner,
+        };
+        let reminders = Reminders {
+            every,
+            max: config.max_reminders,
+            state: Mutex::new(ReminderState {
+                sender: inner.sender(),
+                active: HashMap::new(),
+                closed: false,
+            }),
+        };
+        Wrapper::boxed(inner, reminders)
+    }
+}
+
+pub(crate) struct Reminders {
+    every: Duration,
+    max: Option<u32>,
+    state: Mutex<ReminderState>,
+}
+
+impl Wrapped for Reminders {
+    fn sender(self: Arc<Self>) -> Box<dyn NotifierSender> {
+        Box::new(ReminderSender { reminders: self })
+    }
+
+    fn close(&self) -> BoxedFuture<(), Box<dyn Fail>> {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        // Dropped senders stop timers.
+        state.active.clear();
+        Box::new(futures::future::ok(()))
+    }
+}
+
+struct ReminderState {
+    sender: Box<dyn NotifierSender>,
+    /// Timers of active errors by resource, stopped, when sender is dropped.
+    active: HashMap<String, oneshot::Sender<()>>,
+    /// Errors, raised after shutdown, are not reminded about.
+    closed: bool,
+}
+
+#[derive(Clone)]
+pub(crate) struct ReminderSender {
+    reminders: Arc<Reminders>,
+}
+
+impl NotifierSender for ReminderSender {
+    fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+        let mut state = self.reminders.state.lock().unwrap();
+        match msg.kind {
+            // Reminders of resource itself don't restart timer.
+            MessageKind::Reminder => (),
+            MessageKind::Resolved => {
+                state.active.remove(&msg.resource_name);
+            }
+            MessageKind::New | MessageKind::Changed if !state.closed => {
+                let (stop, stopped) = oneshot::channel::<()>();
+                state.active.insert(msg.resource_name.clone(), stop);
+                let reminders = self.reminders.clone();
+                let every = self.reminders.every;
+                let timer = Interval::new(Instant::now() + every, every)
+                    .map_err(|e| error!("Reminder timer error: {}", e))
+                    .take(self.reminders.max.map(u64::from).unwrap_or(u64::MAX))
+                    .fold(msg.clone(), move |msg, _| {
+                        let reminder = msg.reminder();
+                        debug!("Remind about {}", reminder.resource_name);
+                        let state = reminders.state.lock().unwrap();
+                        tokio::spawn(state.sender.send_message(reminder.clone()).map_err(|_| ()));
+                        Ok(reminder)
+                    });
+                tokio::spawn(timer.select2(stopped).then(|_| Ok(())));
+            }
+            MessageKind::New | MessageKind::Changed => (),
+        }
+        state.sender.send_message(msg)
+    }
+}
@@ -0,0 +1,203 @@
+//! Simple templates with `{{field}}` placeholders, substituted with message fields.
+//!
+//! Available fields: `title`, `body`, `resource`, `state`, `severity`, `description`,
+//! `since`, `timestamp`, `color` (of state), `runbook`, `history` (recent check results) and
+//! `label.<name>`.
+//! Unknown placeholders are left untouched.
+
+use crate::notifier::{Message, MessageKind};
+
+impl Message {
+    fn field(&self, name: &str) -> Option<String> {
+        Some(match name {
+            "title" => self.title.clone(),
+            "body" => self.body.clone(),
+            "resource" => self.resource_name.clone(),
+            "state" => self.kind.as_str().into(),
+            "severity" => self.info.severity().as_str().into(),
+            "description" => self.description.clone(),
+            "since" => self.since.to_rfc3339(),
+            "timestamp" => self.timestamp.to_rfc3339(),
+            "color" => format!("#{:06x}", self.kind.color()),
+            "runbook" => self.info.runbook.clone().unwrap_or_default(),
+            "history" => self
+                .history
+                .iter()
+                .map(|x| match x.error {
+                    Some(ref e) => format!("{} ERROR {}\n", x.timestamp.to_rfc3339(), e),
+                    None => format!("{} OK\n", x.timestamp.to_rfc3339()),
+                })
+                .collect(),
+            _ => match name.strip_prefix("label.") {
+                Some(label) => self.info.labels.get(label)?.clone(),
+                None => return None,
+            },
+        })
+    }
+
+    /// Recent check results as HTML table.
+    fn history_html(&self) -> String {
+        let mut table = String::from("<table><tr><th>Time</th><th>Result</th></tr>");
+        for x in &self.history {
+            let (color, result) = match x.error {
+                Some(ref e) => (MessageKind::New.color(), escape_html(e)),
+                None => (MessageKind::Resolved.color(), "OK".into()),
+            };
+            table.push_str(&format!(
+                "<tr><td>{}</td><td style=\"color: #{:06x}\">{}</td></tr>",
+                x.timestamp.to_rfc3339(),
+                color,
+                result
+            ));
+        }
+        table.push_str("</table>");
+        table
+    }
+}
+
+/// Substitute placeholders in string template.
+pub(crate) fn render(template: &str, msg: &Message) -> String {
+    render_with(template, |name| msg.field(name))
+}
+
+/// Substitute placeholders in HTML template with escaped values. `history` is rendered as table.
+pub(crate) fn render_html(template: &str, msg: &Message) -> String {
+    render_with(template, |name| match name {
+        "history" => Some(msg.history_html()),
+        _ => msg.field(name).map(|x| escape_html(&x)),
+    })
+}
+
+fn render_with<F: Fn(&str) -> Option<String>>(template: &str, field: F) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(x) => start + x,
+            None => break,
+        };
+        result.push_str(&rest[..start]);
+        match field(rest[start + 2..end].trim()) {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&rest[start..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Substitute placeholders in every string (but not key) of JSON template.
+pub(crate) fn render_json(template: &serde_json::Value, msg: &Message) -> serde_json::Value {
+    use serde_json::Value;
+
+    match template {
+        Value::String(s) => Value::String(render(s, msg)),
+        Value::Array(v) => Value::Array(v.iter().map(|x| render_json(x, msg)).collect()),
+        Value::Object(m) => Value::Object(
+            m.iter()
+                .map(|(k, v)| (k.clone(), render_json(v, msg)))
+                .collect(),
+        ),
+        x => x.clone(),
+    }
+}
+
+/// Escape text for inclusion in HTML.
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::Utc;
+
+    use super::*;
+    use crate::notifier::ResourceInfo;
+
+    fn message() -> Message {
+        let mut labels = BTreeMap::new();
+        labels.insert("team".to_string(), "dba".to_string());
+        Message::new(
+            "Error (new) db-1".into(),
+            "Connection <refused>".into(),
+            MessageKind::New,
+            "db-1".into(),
+            "Connection refused".into(),
+            Utc::now(),
+        )
+        .with_context(
+            Vec::new(),
+            ResourceInfo {
+                labels,
+                ..ResourceInfo::default()
+            },
+        )
+    }
+
+    #[test]
+    fn fields_are_substituted() {
+        let msg = message();
+        assert_eq!(
+            render("{{resource}} is {{ state }} ({{label.team}})", &msg),
+            "db-1 is new (dba)"
+        );
+        assert_eq!(render("no placeholders", &msg), "no placeholders");
+        assert_eq!(render("", &msg), "");
+    }
+
+    #[test]
+    fn repeated_fields_are_substituted() {
+        let msg = message();
+        assert_eq!(
+            render("{{resource}}/{{resource}}{{resource}}", &msg),
+            "db-1/db-1db-1"
+        );
+    }
+
+    #[test]
+    fn unknown_placeholders_are_kept() {
+        let msg = message();
+        assert_eq!(
+            render("{{unknown}} {{resource}} {{label.missing}} {{}}", &msg),
+            "{{unknown}} db-1 {{label.missing}} {{}}"
+        );
+    }
+
+    #[test]
+    fn unterminated_placeholder_is_kept() {
+        let msg = message();
+        assert_eq!(render("{{resource}} {{state", &msg), "db-1 {{state");
+        assert_eq!(render("{{resource", &msg), "{{resource");
+        assert_eq!(render("{{resource}} }}", &msg), "db-1 }}");
+    }
+
+    #[test]
+    fn html_fields_are_escaped() {
+        let msg = message();
+        assert_eq!(
+            render_html("<p>{{body}}</p>", &msg),
+            "<p>Connection &lt;refused&gt;</p>"
+        );
+        assert_eq!(render("{{body}}", &msg), "Connection <refused>");
+    }
+
+    #[test]
+    fn json_strings_are_rendered() {
+        let msg = message();
+        let template = serde_json::json!({
+            "{{resource}}": ["{{state}}", 1, {"team": "{{label.team}}"}],
+        });
+        assert_eq!(
+            render_json(&template, &msg),
+            serde_json::json!({
+                "{{resource}}": ["new", 1, {"team": "dba"}],
+            })
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Wrappers, which add behaviour (like batching or fallbacks) to any notifier.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures::{future::lazy, sync::oneshot, Future};
+use tokio_timer::Delay;
+
+use failure::Fail;
+
+use crate::{
+    notifier::{Notifier, NotifierSender},
+    BoxedFuture,
+};
+
+/// State of wrapper, shared by its notifier and senders.
+pub(crate) trait Wrapped: Send + Sync + 'static {
+    fn sender(self: Arc<Self>) -> Box<dyn NotifierSender>;
+
+    /// Send messages, held by wrapper, on shutdown. The following messages must be passed to
+    /// inner notifier as is.
+    fn close(&self) -> BoxedFuture<(), Box<dyn Fail>> {
+        Box::new(futures::future::ok(()))
+    }
+}
+
+/// Notifier, which sends messages through `W` to inner one.
+pub(crate) struct Wrapper<W> {
+    inner: Box<dyn Notifier>,
+    wrapped: Arc<W>,
+}
+
+impl<W: Wrapped> Wrapper<W> {
+    pub(crate) fn boxed(inner: Box<dyn Notifier>, wrapped: W) -> Box<dyn Notifier> {
+        Box::new(Self {
+            inner,
+            wrapped: Arc::new(wrapped),
+        })
+    }
+}
+
+impl<W: Wrapped> Notifier for Wrapper<W> {
+    fn sender(&self) -> Box<dyn NotifierSender> {
+        self.wrapped.clone().sender()
+    }
+
+    fn from_config(_config: serde_yaml::Value) -> Result<Box<dyn Notifier>, Box<dyn Fail>>
+    where
+        Self: Sized,
+    {
+        unreachable!("wrappers are configured with fields of wrapped notifier")
+    }
+
+    fn background(&self) -> Option<BoxedFuture<(), ()>> {
+        self.inner.background()
+    }
+
+    fn shutdown(&self) -> BoxedFuture<(), ()> {
+        let wrapped = self.wrapped.clone();
+        let inner = self.inner.shutdown();
+        // Errors are logged by inner notifier.
+        Box::new(lazy(move || wrapped.close()).then(|_| inner))
+    }
+}
+
+/// Time window, within which wrapper holds messages.
+#[derive(Default)]
+pub(crate) struct Window {
+    /// Stops timer of open window.
+    stop: Option<oneshot::Sender<()>>,
+    /// Wrapper is closed on shutdown and doesn't hold messages anymore.
+    closed: bool,
+}
+
+impl Window {
+    pub(crate) fn is_open(&self) -> bool {
+        self.stop.is_some()
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Open window, which calls `end` after `duration`, or earlier, when it is closed. `end`
+    /// must mark window as `ended`.
+    pub(crate) fn open<F>(&mut self, duration: Duration, end: F)
+    where
+        F: FnOnce() -> BoxedFuture<(), Box<dyn Fail>> + Send + 'static,
+    {
+        let (stop, stopped) = oneshot::channel();
+        self.stop = Some(stop);
+        tokio::spawn(
+            Delay::new(Instant::now() + duration)
+                .select2(stopped)
+                // Error is logged by notifier.
+                .then(|_| end().map_err(|_| ())),
+        );
+    }
+
+    pub(crate) fn ended(&mut self) {
+        self.stop = None;
+    }
+
+    /// End open window right away and don't open new ones.
+    pub(crate) fn close(&mut self) {
+        self.closed = true;
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+}
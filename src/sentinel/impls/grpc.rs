@@ -0,0 +1,554 @@
+use std::{
+    error::Error,
+    net::{SocketAddr, ToSocketAddrs},
+    time::Duration,
+};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut, IntoBuf};
+
+use futures::{
+    future::{err, poll_fn},
+    try_ready, Async, Future, Stream,
+};
+
+use h2::{client, RecvStream};
+
+use http::{HeaderMap, Method, Request};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_timer::Timeout;
+
+use native_tls::TlsConnector;
+
+use log::debug;
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{
+    blocking,
+    sentinel::{Config, ResourceError, Sentinel, SentinelImpl},
+    BoxedFuture, BoxedStream,
+};
+
+const HEALTH_CHECK_PATH: &str = "/grpc.health.v1.Health/Check";
+
+#[derive(Debug, Fail)]
+pub(crate) enum GrpcSentinelError {
+    // Resource failures
+    #[fail(display = "Service health status is UNKNOWN")]
+    StatusUnknown,
+    #[fail(display = "Service health status is NOT_SERVING")]
+    StatusNotServing,
+    #[fail(display = "Service is unknown to health server")]
+    StatusServiceUnknown,
+    #[fail(display = "Unexpected service health status: {}", status)]
+    StatusUnexpected { status: u64 },
+    #[fail(display = "gRPC error (status {}): {}", code, message)]
+    GrpcStatus { code: u32, message: String },
+    #[fail(display = "Transport error: {}", err)]
+    TransportError { err: String },
+    #[fail(display = "Invalid response: {}", reason)]
+    InvalidResponse { reason: String },
+    #[fail(display = "Health check timed out")]
+    Timeout,
+
+    // Build failures
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserializeError { err: serde_yaml::Error },
+    #[fail(display = "TLS connector error: {}", err)]
+    TlsConnectorError { err: native_tls::Error },
+}
+
+impl GrpcSentinelError {
+    fn transport<E: Error>(err: E) -> Self {
+        GrpcSentinelError::TransportError {
+            err: format!("{}", err),
+        }
+    }
+
+    fn invalid_response<S: Into<String>>(reason: S) -> Self {
+        GrpcSentinelError::InvalidResponse {
+            reason: reason.into(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct GrpcSentinelConfig {
+    /// Server address as `host:port`.
+    address: String,
+    /// Name of checked service. Empty name asks for overall server health.
+    #[serde(default)]
+    service: String,
+    #[serde(default)]
+    tls: bool,
+    /// Domain for TLS certificate verification, by default host part of `address`.
+    tls_domain: Option<String>,
+    /// Timeout of single check in milliseconds.
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+}
+
+fn default_timeout() -> u64 {
+    5000
+}
+
+pub(crate) struct GrpcSentinel {
+    address: String,
+    authority: String,
+    tls: Option<(TlsConnector, String)>,
+    request_body: Bytes,
+    timeout: Duration,
+}
+
+impl GrpcSentinel {
+    pub(crate) fn create_sentinel_stream(
+        config: Config,
+    ) -> Result<BoxedStream<(), Box<dyn Error + Send>>, Box<dyn Fail>> {
+        let grpc_config: GrpcSentinelConfig = serde_yaml::from_value(config.config.clone())
+            .map_err(|e| {
+                Box::new(GrpcSentinelError::YamlDeserializeError { err: e }) as Box<dyn Fail>
+            })?;
+        let tls = if grpc_config.tls {
+            let connector = TlsConnector::builder()
+                .request_alpns(&["h2"])
+                .build()
+                .map_err(|e| {
+                    Box::new(GrpcSentinelError::TlsConnectorError { err: e }) as Box<dyn Fail>
+                })?;
+            let domain = grpc_config.tls_domain.clone().unwrap_or_else(|| {
+                grpc_config
+                    .address
+                    .rsplitn(2, ':')
+                    .last()
+                    .unwrap_or_default()
+                    .to_string()
+            });
+            Some((connector, domain))
+        } else {
+            None
+        };
+        let sentinel_impl = Box::new(Self {
+            authority: grpc_config.address.clone(),
+            address: grpc_config.address,
+            tls,
+            request_body: encode_health_check_request(&grpc_config.service),
+            timeout: Duration::from_millis(grpc_config.timeout),
+        });
+
+        let sent = Sentinel::new(sentinel_impl, config);
+        Ok(Box::new(sent))
+    }
+
+    fn connect(&self) -> impl Future<Item = TcpStream, Error = GrpcSentinelError> {
+        let address = self.address.clone();
+        blocking(move || address.to_socket_addrs().map(|mut x| x.next()))
+            .map_err(GrpcSentinelError::transport)
+            .and_then(|res| match res {
+                Ok(Some(addr)) => Ok(addr),
+                Ok(None) => Err(GrpcSentinelError::TransportError {
+                    err: "address resolved to nothing".into(),
+                }),
+                Err(e) => Err(GrpcSentinelError::transport(e)),
+            })
+            .and_then(|addr: SocketAddr| {
+                TcpStream::connect(&addr).map_err(GrpcSentinelError::transport)
+            })
+    }
+
+    fn check(&self) -> BoxedFuture<u64, GrpcSentinelError> {
+        let scheme = if self.tls.is_some() { "https" } else { "http" };
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(format!(
+                "{}://{}{}",
+                scheme, self.authority, HEALTH_CHECK_PATH
+            ))
+            .header("content-type", "application/grpc")
+            .header("te", "trailers")
+            .body(());
+        let request = match request {
+            Ok(x) => x,
+            Err(e) => return Box::new(err(GrpcSentinelError::invalid_response(format!("{}", e)))),
+        };
+        let body = self.request_body.clone();
+        let tcp = self.connect();
+        match self.tls {
+            Some((ref connector, ref domain)) => {
+                let connector = tokio_tls::TlsConnector::from(connector.clone());
+                let domain = domain.clone();
+                Box::new(
+                    tcp.and_then(move |tcp| {
+                        connector
+                            .connect(&domain, tcp)
+                            .map_err(GrpcSentinelError::transport)
+                    })
+                    .and_then(move |tls| health_check(tls, request, body)),
+                )
+            }
+            None => Box::new(tcp.and_then(move |tcp| health_check(tcp, request, body))),
+        }
+    }
+}
+
+impl SentinelImpl for GrpcSentinel {
+    type ResourceOk = ();
+    type ResourceErr = GrpcSentinelError;
+    type SentinelErr = tokio_timer::Error;
+
+    fn produce_future(
+        &self,
+    ) -> BoxedFuture<Result<Self::ResourceOk, Self::ResourceErr>, Self::SentinelErr> {
+        Box::new(Timeout::new(self.check(), self.timeout).then(|res| {
+            debug!("gRPC health check result: {:?}", res);
+            match res {
+                Ok(status) => Ok(serving_status(status)),
+                Err(ref e) if e.is_elapsed() => Ok(Err(GrpcSentinelError::Timeout)),
+                Err(e) if e.is_inner() => Ok(Err(e.into_inner().unwrap())),
+                Err(e) => Err(e.into_timer().unwrap()),
+            }
+        }))
+    }
+
+    fn compare_errors(&self, left: &Self::ResourceErr, right: &Self::ResourceErr) -> bool {
+        match (left, right) {
+            (
+                GrpcSentinelError::StatusUnexpected { status: l },
+                GrpcSentinelError::StatusUnexpected { status: r },
+            ) => l == r,
+            (
+                GrpcSentinelError::GrpcStatus { code: l, .. },
+                GrpcSentinelError::GrpcStatus { code: r, .. },
+            ) => l == r,
+            // Different transport failures (refused, reset, TLS) are the same outage.
+            (l, r) => std::mem::discriminant(l) == std::mem::discriminant(r),
+        }
+    }
+}
+
+impl ResourceError for GrpcSentinelError {
+    fn description(&self) -> String {
+        format!("{}", self)
+    }
+}
+
+/// Check `ServingStatus` of health check response.
+fn serving_status(status: u64) -> Result<(), GrpcSentinelError> {
+    match status {
+        1 => Ok(()),
+        0 => Err(GrpcSentinelError::StatusUnknown),
+        2 => Err(GrpcSentinelError::StatusNotServing),
+        3 => Err(GrpcSentinelError::StatusServiceUnknown),
+        status => Err(GrpcSentinelError::StatusUnexpected { status }),
+    }
+}
+
+/// Perform `Health/Check` call over established connection and return reported status.
+fn health_check<T>(
+    io: T,
+    request: Request<()>,
+    body: Bytes,
+) -> impl Future<Item = u64, Error = GrpcSentinelError>
+where
+    T: AsyncRead + AsyncWrite + Send + 'static,
+{
+    client::handshake(io)
+        .map_err(GrpcSentinelError::transport)
+        .and_then(|(send_request, connection)| {
+            tokio::spawn(connection.map_err(|e| debug!("gRPC connection error: {}", e)));
+            send_request.ready().map_err(GrpcSentinelError::transport)
+        })
+        .and_then(move |mut send_request| {
+            let (response, mut stream) = send_request
+                .send_request(request, false)
+                .map_err(GrpcSentinelError::transport)?;
+            stream
+                .send_data(body, true)
+                .map_err(GrpcSentinelError::transport)?;
+            Ok(response.map_err(GrpcSentinelError::transport))
+        })
+        .flatten()
+        .and_then(|response| {
+            let (parts, body) = response.into_parts();
+            if !parts.status.is_success() {
+                return Err(GrpcSentinelError::invalid_response(format!(
+                    "HTTP status {}",
+                    parts.status
+                )));
+            }
+            // Trailers-only response carries gRPC status in headers.
+            check_grpc_status(&parts.headers)?;
+            Ok(read_body(body))
+        })
+        .flatten()
+        .and_then(|(data, trailers)| {
+            if let Some(ref trailers) = trailers {
+                check_grpc_status(trailers)?;
+            }
+            decode_health_check_response(data)
+        })
+}
+
+/// Read whole response body and trailers.
+fn read_body(
+    mut body: RecvStream,
+) -> impl Future<Item = (Bytes, Option<HeaderMap>), Error = GrpcSentinelError> {
+    let mut data = BytesMut::new();
+    let mut data_done = false;
+    poll_fn(move || {
+        while !data_done {
+            match try_ready!(body.poll()) {
+                Some(chunk) => {
+                    let _ = body.release_capacity().release_capacity(chunk.len());
+                    data.extend_from_slice(&chunk);
+                }
+                None => data_done = true,
+            }
+        }
+        let trailers = try_ready!(body.poll_trailers());
+        Ok(Async::Ready((data.take().freeze(), trailers)))
+    })
+    .map_err(GrpcSentinelError::transport::<h2::Error>)
+}
+
+fn check_grpc_status(headers: &HeaderMap) -> Result<(), GrpcSentinelError> {
+    let code = match headers.get("grpc-status") {
+        Some(x) => x
+            .to_str()
+            .ok()
+            .and_then(|x| x.parse::<u32>().ok())
+            .ok_or_else(|| GrpcSentinelError::invalid_response("malformed grpc-status"))?,
+        None => return Ok(()),
+    };
+    if code == 0 {
+        return Ok(());
+    }
+    let message = headers
+        .get("grpc-message")
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    Err(GrpcSentinelError::GrpcStatus { code, message })
+}
+
+/// Encode length-prefixed `HealthCheckRequest { service }` message.
+fn encode_health_check_request(service: &str) -> Bytes {
+    let mut message = BytesMut::with_capacity(service.len() + 16);
+    if !service.is_empty() {
+        // Field 1, wire type 2 (length-delimited).
+        message.put_u8(0x0a);
+        put_varint(&mut message, service.len() as u64);
+        message.put_slice(service.as_bytes());
+    }
+    let mut framed = BytesMut::with_capacity(message.len() + 5);
+    framed.put_u8(0);
+    framed.put_u32_be(message.len() as u32);
+    framed.put_slice(&message);
+    framed.freeze()
+}
+
+/// Decode length-prefixed `HealthCheckResponse` message and return its status.
+fn decode_health_check_response(data: Bytes) -> Result<u64, GrpcSentinelError> {
+    if data.len() < 5 {
+        return Err(GrpcSentinelError::invalid_response("empty message"));
+    }
+    let mut buf = data.into_buf();
+    if buf.get_u8() != 0 {
+        return Err(GrpcSentinelError::invalid_response(
+            "compressed messages are not supported",
+        ));
+    }
+    let len = buf.get_u32_be() as usize;
+    if buf.remaining() < len {
+        return Err(GrpcSentinelError::invalid_response("truncated message"));
+    }
+    // Missing status field means default value, UNKNOWN.
+    let mut status = 0;
+    let mut message = buf.take(len);
+    while message.has_remaining() {
+        let key = get_varint(&mut message)?;
+        match (key >> 3, key & 0x7) {
+            (1, 0) => status = get_varint(&mut message)?,
+            // Skip unknown fields.
+            (_, 0) => {
+                get_varint(&mut message)?;
+            }
+            (_, 1) => skip(&mut message, 8)?,
+            (_, 2) => {
+                let len = get_varint(&mut message)? as usize;
+                skip(&mut message, len)?
+            }
+            (_, 5) => skip(&mut message, 4)?,
+            (_, wire_type) => {
+                return Err(GrpcSentinelError::invalid_response(format!(
+                    "unsupported wire type {}",
+                    wire_type
+                )))
+            }
+        }
+    }
+    Ok(status)
+}
+
+fn put_varint(buf: &mut BytesMut, mut value: u64) {
+    while value >= 0x80 {
+        buf.put_u8((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.put_u8(value as u8);
+}
+
+fn get_varint<B: Buf>(buf: &mut B) -> Result<u64, GrpcSentinelError> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        if !buf.has_remaining() {
+            break;
+        }
+        let byte = buf.get_u8();
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(GrpcSentinelError::invalid_response("malformed varint"))
+}
+
+fn skip<B: Buf>(buf: &mut B, len: usize) -> Result<(), GrpcSentinelError> {
+    if buf.remaining() < len {
+        return Err(GrpcSentinelError::invalid_response("truncated field"));
+    }
+    buf.advance(len);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(value: u64) -> Vec<u8> {
+        let mut buf = BytesMut::new();
+        put_varint(&mut buf, value);
+        buf.to_vec()
+    }
+
+    /// Length-prefixed message.
+    fn frame(message: &[u8]) -> Bytes {
+        let mut framed = BytesMut::new();
+        framed.put_u8(0);
+        framed.put_u32_be(message.len() as u32);
+        framed.put_slice(message);
+        framed.freeze()
+    }
+
+    fn is_invalid<T>(res: Result<T, GrpcSentinelError>) -> bool {
+        matches!(res, Err(GrpcSentinelError::InvalidResponse { .. }))
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        assert_eq!(varint(0), vec![0x00]);
+        assert_eq!(varint(127), vec![0x7f]);
+        assert_eq!(varint(128), vec![0x80, 0x01]);
+        assert_eq!(varint(300), vec![0xac, 0x02]);
+        for &value in &[
+            0,
+            1,
+            127,
+            128,
+            300,
+            16_383,
+            16_384,
+            u64::from(u32::MAX),
+            u64::MAX,
+        ] {
+            let mut buf = Bytes::from(varint(value)).into_buf();
+            assert_eq!(get_varint(&mut buf).unwrap(), value);
+            assert!(!buf.has_remaining());
+        }
+        assert_eq!(varint(u64::MAX).len(), 10);
+    }
+
+    #[test]
+    fn varint_is_truncated() {
+        assert!(is_invalid(get_varint(&mut Bytes::new().into_buf())));
+        assert!(is_invalid(get_varint(
+            &mut Bytes::from(vec![0x80, 0x80]).into_buf()
+        )));
+    }
+
+    #[test]
+    fn request_is_encoded() {
+        assert_eq!(
+            encode_health_check_request("").to_vec(),
+            vec![0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            encode_health_check_request("svc").to_vec(),
+            vec![0, 0, 0, 0, 5, 0x0a, 3, b's', b'v', b'c']
+        );
+    }
+
+    #[test]
+    fn response_status_is_decoded() {
+        for status in 0..=4 {
+            let data = frame(&[0x08, status as u8]);
+            assert_eq!(decode_health_check_response(data).unwrap(), status);
+        }
+        // Default value is omitted.
+        assert_eq!(decode_health_check_response(frame(&[])).unwrap(), 0);
+        // Unknown fields of every wire type are skipped.
+        let message = [
+            0x10, 0x96, 0x01, // field 2, varint
+            0x19, 0, 0, 0, 0, 0, 0, 0, 0, // field 3, 64-bit
+            0x22, 2, b'o', b'k', // field 4, length-delimited
+            0x2d, 0, 0, 0, 0, // field 5, 32-bit
+            0x08, 0x01,
+        ];
+        assert_eq!(decode_health_check_response(frame(&message)).unwrap(), 1);
+    }
+
+    #[test]
+    fn response_is_validated() {
+        assert!(is_invalid(decode_health_check_response(Bytes::from(vec![
+            0, 0, 0
+        ]))));
+        let mut compressed = frame(&[0x08, 0x01]).to_vec();
+        compressed[0] = 1;
+        assert!(is_invalid(decode_health_check_response(compressed.into())));
+        // Length prefix is longer than message.
+        let mut truncated = frame(&[0x08, 0x01]).to_vec();
+        truncated.pop();
+        assert!(is_invalid(decode_health_check_response(truncated.into())));
+        assert!(is_invalid(decode_health_check_response(frame(&[0x08]))));
+        assert!(is_invalid(decode_health_check_response(frame(&[
+            0x22, 5, b'o'
+        ]))));
+        assert!(is_invalid(decode_health_check_response(frame(&[0x0b]))));
+    }
+
+    #[test]
+    fn serving_status_is_checked() {
+        assert!(serving_status(1).is_ok());
+        assert!(matches!(
+            serving_status(0),
+            Err(GrpcSentinelError::StatusUnknown)
+        ));
+        assert!(matches!(
+            serving_status(2),
+            Err(GrpcSentinelError::StatusNotServing)
+        ));
+        assert!(matches!(
+            serving_status(3),
+            Err(GrpcSentinelError::StatusServiceUnknown)
+        ));
+        assert!(matches!(
+            serving_status(7),
+            Err(GrpcSentinelError::StatusUnexpected { status: 7 })
+        ));
+    }
+}
@@ -1,11 +1,13 @@
-use std::{convert::TryFrom, error::Error};
+use std::{convert::TryFrom, error::Error, fmt};
 
 use reqwest::{
-    r#async::{Client, ClientBuilder, Response},
-    Url,
+    r#async::{Client, ClientBuilder},
+    StatusCode, Url,
 };
 
-use futures::Future;
+use futures::{future::join_all, Future};
+
+use log::debug;
 
 use serde::Deserialize;
 
@@ -23,6 +25,12 @@ pub(crate) enum HttpSentinelError {
     ReqwestHttpError { err: reqwest::Error },
     #[fail(display = "Non-successful HTTP code: {}", code)]
     NonSuccessfulHttpCode { code: u16 },
+    #[fail(display = "{} of {} targets failed:{}", failed, total, targets)]
+    TargetsFailed {
+        failed: usize,
+        total: usize,
+        targets: HttpTargetStatuses,
+    },
 
     // Build failures
     #[fail(display = "Invalid status code: {}", code)]
@@ -33,6 +41,40 @@ pub(crate) enum HttpSentinelError {
     ReqwestClientError { err: reqwest::Error },
     #[fail(display = "Url parse error: {}", err)]
     UrlParseError { err: reqwest::UrlError },
+    #[fail(display = "No target URLs configured")]
+    NoTargets,
+    #[fail(
+        display = "Policy threshold {} is not less than number of targets {}",
+        k, total
+    )]
+    InvalidPolicyThreshold { k: usize, total: usize },
+}
+
+/// Target and its check result.
+type TargetStatus = (Url, Result<StatusCode, HttpSentinelError>);
+
+/// Status of every target of a multi-target resource, used in error reports.
+#[derive(Debug)]
+pub(crate) struct HttpTargetStatuses(Vec<TargetStatus>);
+
+impl HttpTargetStatuses {
+    fn failed(&self) -> impl Iterator<Item = (&Url, &HttpSentinelError)> {
+        self.0
+            .iter()
+            .filter_map(|(url, res)| res.as_ref().err().map(|e| (url, e)))
+    }
+}
+
+impl fmt::Display for HttpTargetStatuses {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (url, res) in self.0.iter() {
+            match res {
+                Ok(code) => write!(f, "\n  {}: OK ({})", url, code.as_u16())?,
+                Err(e) => write!(f, "\n  {}: FAILED ({})", url, e)?,
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -41,9 +83,63 @@ enum HttpCodesRaw {
     Error(Vec<u16>),
 }
 
+/// When multi-target resource is considered failed.
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+enum HttpPolicy {
+    /// At least one target failed.
+    #[default]
+    Any,
+    /// Every target failed.
+    All,
+    /// More than K targets failed.
+    MoreThan(usize),
+}
+
+impl HttpPolicy {
+    fn is_failed(self, failed: usize, total: usize) -> bool {
+        match self {
+            HttpPolicy::Any => failed > 0,
+            HttpPolicy::All => failed == total,
+            HttpPolicy::MoreThan(k) => failed > k,
+        }
+    }
+
+    /// Result of resource from results of its targets.
+    fn aggregate(
+        self,
+        mut statuses: Vec<TargetStatus>,
+    ) -> Result<Vec<StatusCode>, HttpSentinelError> {
+        let total = statuses.len();
+        // Single target reports its own error, so messages stay the same as
+        // for plain one-URL resources.
+        if total == 1 {
+            return statuses.pop().unwrap().1.map(|code| vec![code]);
+        }
+        let statuses = HttpTargetStatuses(statuses);
+        let failed = statuses.failed().count();
+        if self.is_failed(failed, total) {
+            Err(HttpSentinelError::TargetsFailed {
+                failed,
+                total,
+                targets: statuses,
+            })
+        } else {
+            Ok(statuses
+                .0
+                .into_iter()
+                .filter_map(|(_, res)| res.ok())
+                .collect())
+        }
+    }
+}
+
 #[derive(Deserialize, Clone, Debug)]
 struct HttpSentinelConfig {
-    url: String,
+    url: Option<String>,
+    #[serde(default)]
+    urls: Vec<String>,
+    #[serde(default)]
+    policy: HttpPolicy,
     codes: HttpCodesRaw,
 }
 
@@ -70,8 +166,25 @@ impl TryFrom<HttpCodesRaw> for HttpCodes {
     }
 }
 
+impl HttpCodes {
+    fn check(&self, code: StatusCode) -> Result<StatusCode, HttpSentinelError> {
+        let is_failed = match self {
+            HttpCodes::Success(ref codes) => !codes.contains(&code),
+            HttpCodes::Error(ref codes) => codes.contains(&code),
+        };
+        if is_failed {
+            Err(HttpSentinelError::NonSuccessfulHttpCode {
+                code: code.as_u16(),
+            })
+        } else {
+            Ok(code)
+        }
+    }
+}
+
 pub(crate) struct HttpSentinel {
-    url: Url,
+    urls: Vec<Url>,
+    policy: HttpPolicy,
     client: Client,
     codes: HttpCodes,
 }
@@ -80,84 +193,108 @@ impl HttpSentinel {
     pub(crate) fn create_sentinel_stream(
         config: Config,
     ) -> Result<BoxedStream<(), Box<dyn Error + Send>>, Box<dyn Fail>> {
-        let http_config: HttpSentinelConfig =
-            serde_yaml::from_value(config.config).map_err(|e| {
+        let http_config: HttpSentinelConfig = serde_yaml::from_value(config.config.clone())
+            .map_err(|e| {
                 Box::new(HttpSentinelError::YamlDeserializeError { err: e }) as Box<dyn Fail>
             })?;
         let client = ClientBuilder::new().build().map_err(|e| {
             Box::new(HttpSentinelError::ReqwestClientError { err: e }) as Box<dyn Fail>
         })?;
-        let url = Url::parse(&http_config.url)
-            .map_err(|e| Box::new(HttpSentinelError::UrlParseError { err: e }) as Box<dyn Fail>)?;;
+        let urls = http_config
+            .url
+            .iter()
+            .chain(http_config.urls.iter())
+            .map(|x| Url::parse(x))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Box::new(HttpSentinelError::UrlParseError { err: e }) as Box<dyn Fail>)?;
+        if urls.is_empty() {
+            return Err(Box::new(HttpSentinelError::NoTargets) as Box<dyn Fail>);
+        }
+        if let HttpPolicy::MoreThan(k) = http_config.policy {
+            if k >= urls.len() {
+                return Err(Box::new(HttpSentinelError::InvalidPolicyThreshold {
+                    k,
+                    total: urls.len(),
+                }) as Box<dyn Fail>);
+            }
+        }
         let codes =
             HttpCodes::try_from(http_config.codes).map_err(|e| Box::new(e) as Box<dyn Fail>)?;
-        let sentinel_impl = Box::new(Self { url, client, codes });
-
-        let sent = Sentinel::new(
-            sentinel_impl,
-            config.interval,
-            config.notifiers,
-            http_config.url,
-        );
+        let sentinel_impl = Box::new(Self {
+            urls,
+            policy: http_config.policy,
+            client,
+            codes,
+        });
+
+        let sent = Sentinel::new(sentinel_impl, config);
         Ok(Box::new(sent))
     }
+
+    fn check_target(&self, url: Url) -> impl Future<Item = TargetStatus, Error = reqwest::Error> {
+        let codes = self.codes.clone();
+        self.client
+            .get(url.clone())
+            .send()
+            .map_err(|e| HttpSentinelError::ReqwestHttpError { err: e })
+            .and_then(move |res| codes.check(res.status()))
+            .then(move |res| {
+                debug!("HTTP target {}: {:?}", url, res);
+                Ok((url, res))
+            })
+    }
 }
 
 impl SentinelImpl for HttpSentinel {
-    type ResourceOk = Response;
+    type ResourceOk = Vec<StatusCode>;
     type ResourceErr = HttpSentinelError;
     type SentinelErr = reqwest::Error;
 
     fn produce_future(
         &self,
     ) -> BoxedFuture<Result<Self::ResourceOk, Self::ResourceErr>, Self::SentinelErr> {
-        let codes = self.codes.clone();
-        Box::new(
-            self.client
-                .get(self.url.clone())
-                .send()
-                .map_err(|e| HttpSentinelError::ReqwestHttpError { err: e })
-                .and_then(move |res| match codes {
-                    HttpCodes::Success(ref codes) => {
-                        if !codes.contains(&res.status()) {
-                            Err(HttpSentinelError::NonSuccessfulHttpCode {
-                                code: res.status().as_u16(),
-                            })
-                        } else {
-                            Ok(res)
-                        }
-                    }
-                    HttpCodes::Error(ref codes) => {
-                        if codes.contains(&res.status()) {
-                            Err(HttpSentinelError::NonSuccessfulHttpCode {
-                                code: res.status().as_u16(),
-                            })
-                        } else {
-                            Ok(res)
-                        }
-                    }
-                })
-                .then(|res| dbg!(Ok(res))),
-        )
+        let policy = self.policy;
+        let checks = self
+            .urls
+            .iter()
+            .map(|url| self.check_target(url.clone()))
+            .collect::<Vec<_>>();
+        Box::new(join_all(checks).map(move |statuses| policy.aggregate(statuses)))
     }
 
     fn compare_errors(&self, left: &Self::ResourceErr, right: &Self::ResourceErr) -> bool {
-        match (left, right) {
-            (
-                HttpSentinelError::NonSuccessfulHttpCode { code: l },
-                HttpSentinelError::NonSuccessfulHttpCode { code: r },
-            ) => l == r,
-            (
-                HttpSentinelError::NonSuccessfulHttpCode { .. },
-                HttpSentinelError::ReqwestHttpError { .. },
-            ) => false,
-            (
-                HttpSentinelError::ReqwestHttpError { .. },
-                HttpSentinelError::NonSuccessfulHttpCode { .. },
-            ) => false,
-            // TODO: make correct comparsion
-            _ => true,
+        compare_errors(left, right)
+    }
+}
+
+fn compare_errors(left: &HttpSentinelError, right: &HttpSentinelError) -> bool {
+    match (left, right) {
+        (
+            HttpSentinelError::NonSuccessfulHttpCode { code: l },
+            HttpSentinelError::NonSuccessfulHttpCode { code: r },
+        ) => l == r,
+        (
+            HttpSentinelError::NonSuccessfulHttpCode { .. },
+            HttpSentinelError::ReqwestHttpError { .. },
+        ) => false,
+        (
+            HttpSentinelError::ReqwestHttpError { .. },
+            HttpSentinelError::NonSuccessfulHttpCode { .. },
+        ) => false,
+        // Same set of failed targets, each failed the same way.
+        (
+            HttpSentinelError::TargetsFailed { targets: l, .. },
+            HttpSentinelError::TargetsFailed { targets: r, .. },
+        ) => {
+            l.failed().count() == r.failed().count()
+                && l.failed()
+                    .zip(r.failed())
+                    .all(|((lu, le), (ru, re))| lu == ru && compare_errors(le, re))
         }
+        (HttpSentinelError::TargetsFailed { .. }, _) => false,
+        (_, HttpSentinelError::TargetsFailed { .. }) => false,
+        // TODO: make correct comparsion
+        _ => true,
     }
 }
 
@@ -166,3 +303,137 @@ impl ResourceError for HttpSentinelError {
         format!("{}", self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(raw: HttpCodesRaw) -> HttpCodes {
+        HttpCodes::try_from(raw).unwrap()
+    }
+
+    /// Results of targets, each is OK or failed with its code.
+    fn statuses(results: &[Result<u16, u16>]) -> Vec<TargetStatus> {
+        results
+            .iter()
+            .enumerate()
+            .map(|(i, res)| {
+                let url = Url::parse(&format!("http://10.0.0.{}/health", i + 1)).unwrap();
+                let res = match *res {
+                    Ok(code) => Ok(StatusCode::from_u16(code).unwrap()),
+                    Err(code) => Err(HttpSentinelError::NonSuccessfulHttpCode { code }),
+                };
+                (url, res)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn success_codes_match() {
+        let codes = codes(HttpCodesRaw::Success(vec![200, 204]));
+        assert!(codes.check(StatusCode::OK).is_ok());
+        assert!(codes.check(StatusCode::NO_CONTENT).is_ok());
+        assert!(matches!(
+            codes.check(StatusCode::NOT_FOUND),
+            Err(HttpSentinelError::NonSuccessfulHttpCode { code: 404 })
+        ));
+    }
+
+    #[test]
+    fn error_codes_match() {
+        let codes = codes(HttpCodesRaw::Error(vec![500, 503]));
+        assert!(codes.check(StatusCode::OK).is_ok());
+        assert!(codes.check(StatusCode::NOT_FOUND).is_ok());
+        assert!(matches!(
+            codes.check(StatusCode::SERVICE_UNAVAILABLE),
+            Err(HttpSentinelError::NonSuccessfulHttpCode { code: 503 })
+        ));
+    }
+
+    #[test]
+    fn invalid_code_is_rejected() {
+        assert!(matches!(
+            HttpCodes::try_from(HttpCodesRaw::Success(vec![200, 1000])),
+            Err(HttpSentinelError::InvalidStatusCode { code: 1000 })
+        ));
+    }
+
+    #[test]
+    fn policy_is_applied_to_targets() {
+        let table = vec![
+            (HttpPolicy::Any, vec![Ok(200), Ok(200), Ok(200)], false),
+            (HttpPolicy::Any, vec![Ok(200), Err(503), Ok(200)], true),
+            (HttpPolicy::All, vec![Err(503), Err(503), Ok(200)], false),
+            (HttpPolicy::All, vec![Err(503), Err(500), Err(503)], true),
+            (
+                HttpPolicy::MoreThan(1),
+                vec![Err(503), Ok(200), Ok(200)],
+                false,
+            ),
+            (
+                HttpPolicy::MoreThan(1),
+                vec![Err(503), Err(503), Ok(200)],
+                true,
+            ),
+            (
+                HttpPolicy::MoreThan(0),
+                vec![Ok(200), Ok(200), Err(503)],
+                true,
+            ),
+        ];
+        for (policy, results, failed) in table {
+            let res = policy.aggregate(statuses(&results));
+            assert_eq!(res.is_err(), failed, "{:?} of {:?}", policy, results);
+        }
+    }
+
+    #[test]
+    fn aggregate_reports_failed_targets() {
+        let res = HttpPolicy::Any.aggregate(statuses(&[Ok(200), Err(503), Ok(204)]));
+        match res {
+            Err(HttpSentinelError::TargetsFailed { failed, total, .. }) => {
+                assert_eq!((failed, total), (1, 3))
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+        let res = HttpPolicy::All.aggregate(statuses(&[Ok(200), Err(503), Ok(204)]));
+        assert_eq!(res.unwrap(), vec![StatusCode::OK, StatusCode::NO_CONTENT]);
+    }
+
+    #[test]
+    fn single_target_reports_own_error() {
+        for policy in &[HttpPolicy::Any, HttpPolicy::All] {
+            assert!(matches!(
+                policy.aggregate(statuses(&[Err(503)])),
+                Err(HttpSentinelError::NonSuccessfulHttpCode { code: 503 })
+            ));
+        }
+    }
+
+    #[test]
+    fn errors_of_same_targets_are_equal() {
+        let failed = |results: &[Result<u16, u16>]| {
+            HttpPolicy::Any.aggregate(statuses(results)).unwrap_err()
+        };
+        let error = failed(&[Ok(200), Err(503), Ok(200)]);
+        assert!(compare_errors(
+            &error,
+            &failed(&[Ok(200), Err(503), Ok(200)])
+        ));
+        assert!(!compare_errors(
+            &error,
+            &failed(&[Ok(200), Err(500), Ok(200)])
+        ));
+        assert!(!compare_errors(
+            &error,
+            &failed(&[Err(503), Ok(200), Ok(200)])
+        ));
+        assert!(!compare_errors(
+            &error,
+            &failed(&[Ok(200), Err(503), Err(503)])
+        ));
+        let code = HttpSentinelError::NonSuccessfulHttpCode { code: 503 };
+        assert!(!compare_errors(&error, &code));
+        assert!(compare_errors(&code, &failed(&[Err(503)])));
+    }
+}
@@ -0,0 +1,694 @@
+use std::{
+    error::Error,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use lettre::{smtp::authentication::Credentials, SmtpClient, Transport};
+
+use futures::{
+    future::{loop_fn, ok, Either, Loop},
+    Future,
+};
+use tokio_timer::Delay;
+
+use native_tls::TlsConnector;
+
+use log::debug;
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{
+    blocking,
+    sentinel::{Config, ResourceError, Sentinel, SentinelImpl},
+    BoxedFuture, BoxedStream,
+};
+
+#[derive(Debug, Fail)]
+pub(crate) enum MailRoundtripSentinelError {
+    // Resource failures
+    #[fail(display = "Failed to send probe email: {}", err)]
+    SendFailed { err: String },
+    #[fail(display = "Mailbox error: {}", err)]
+    MailboxError { err: String },
+    #[fail(display = "Probe email was not delivered within {} ms", timeout)]
+    NotDelivered { timeout: u64 },
+    #[fail(
+        display = "Probe email delivered in {} ms, threshold is {} ms",
+        elapsed, threshold
+    )]
+    SlowDelivery { elapsed: u64, threshold: u64 },
+
+    // Build failures
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserializeError { err: serde_yaml::Error },
+    #[fail(display = "SMTP client error: {}", err)]
+    SmtpClientError { err: lettre::smtp::error::Error },
+    #[fail(display = "TLS connector error: {}", err)]
+    TlsConnectorError { err: native_tls::Error },
+}
+
+impl MailRoundtripSentinelError {
+    fn mailbox<E: Error>(err: E) -> Self {
+        MailRoundtripSentinelError::MailboxError {
+            err: format!("{}", err),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct SmtpServerConfig {
+    host: String,
+    login: String,
+    pwd: String,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+enum MailboxProtocol {
+    Imap,
+    Pop3,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct MailboxConfig {
+    protocol: MailboxProtocol,
+    host: String,
+    /// By default 993/143 for IMAP and 995/110 for POP3, depending on `tls`.
+    port: Option<u16>,
+    #[serde(default = "default_tls")]
+    tls: bool,
+    login: String,
+    pwd: String,
+    /// IMAP folder, where probe is expected.
+    #[serde(default = "default_folder")]
+    folder: String,
+}
+
+fn default_tls() -> bool {
+    true
+}
+
+fn default_folder() -> String {
+    "INBOX".into()
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct MailRoundtripSentinelConfig {
+    smtp: SmtpServerConfig,
+    from: String,
+    to: String,
+    mailbox: MailboxConfig,
+    /// Maximum acceptable delivery time in milliseconds.
+    threshold: u64,
+    /// Time in milliseconds after which probe is considered lost.
+    timeout: u64,
+    /// Interval between mailbox polls in milliseconds.
+    #[serde(default = "default_poll_interval")]
+    poll_interval: u64,
+}
+
+fn default_poll_interval() -> u64 {
+    5000
+}
+
+pub(crate) struct MailRoundtripSentinel {
+    resource_name: String,
+    smtp_client: SmtpClient,
+    from: String,
+    to: String,
+    mailbox: Mailbox,
+    threshold: Duration,
+    timeout: Duration,
+    poll_interval: Duration,
+    probe_counter: AtomicUsize,
+}
+
+impl MailRoundtripSentinel {
+    pub(crate) fn create_sentinel_stream(
+        config: Config,
+    ) -> Result<BoxedStream<(), Box<dyn Error + Send>>, Box<dyn Fail>> {
+        let mail_config: MailRoundtripSentinelConfig =
+            serde_yaml::from_value(config.config.clone()).map_err(|e| {
+                Box::new(MailRoundtripSentinelError::YamlDeserializeError { err: e })
+                    as Box<dyn Fail>
+            })?;
+        let SmtpServerConfig { host, login, pwd } = mail_config.smtp;
+        let smtp_client = SmtpClient::new_simple(&host)
+            .map_err(|e| {
+                Box::new(MailRoundtripSentinelError::SmtpClientError { err: e }) as Box<dyn Fail>
+            })?
+            .credentials(Credentials::new(login, pwd));
+        let tls = if mail_config.mailbox.tls {
+            Some(TlsConnector::new().map_err(|e| {
+                Box::new(MailRoundtripSentinelError::TlsConnectorError { err: e }) as Box<dyn Fail>
+            })?)
+        } else {
+            None
+        };
+        let sentinel_impl = Box::new(Self {
+            resource_name: config.name.clone(),
+            smtp_client,
+            from: mail_config.from,
+            to: mail_config.to,
+            mailbox: Mailbox {
+                config: mail_config.mailbox,
+                tls,
+            },
+            threshold: Duration::from_millis(mail_config.threshold),
+            timeout: Duration::from_millis(mail_config.timeout),
+            poll_interval: Duration::from_millis(mail_config.poll_interval),
+            probe_counter: AtomicUsize::new(0),
+        });
+
+        let sent = Sentinel::new(sentinel_impl, config);
+        Ok(Box::new(sent))
+    }
+
+    /// Common prefix of tags of all probes of resource.
+    fn probe_prefix(&self) -> String {
+        format!(
+            "sentinel-probe-{}-",
+            self.resource_name
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        )
+    }
+
+    /// Unique tag, which is put into subject of probe email in brackets.
+    fn next_probe_tag(&self) -> String {
+        format!(
+            "{}{}-{}",
+            self.probe_prefix(),
+            chrono::Utc::now().timestamp(),
+            self.probe_counter.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+
+    fn send_probe(
+        &self,
+        tag: &str,
+    ) -> impl Future<Item = Result<(), MailRoundtripSentinelError>, Error = io::Error> {
+        let email = lettre_email::Email::builder()
+            .to(self.to.clone())
+            .from(self.from.clone())
+            .subject(format!("Sentinel mail round-trip probe [{}]", tag))
+            .text(format!(
+                "This email was sent by Sentinel to check mail delivery and will be deleted automatically.\n{}",
+                tag
+            ))
+            .build();
+        let client = self.smtp_client.clone();
+        blocking(move || {
+            let email = email.map_err(|e| MailRoundtripSentinelError::SendFailed {
+                err: format!("{}", e),
+            })?;
+            client
+                .transport()
+                .send(email.into())
+                .map(|_| ())
+                .map_err(|e| MailRoundtripSentinelError::SendFailed {
+                    err: format!("{}", e),
+                })
+        })
+        .map_err(io::Error::other)
+    }
+}
+
+impl SentinelImpl for MailRoundtripSentinel {
+    type ResourceOk = Duration;
+    type ResourceErr = MailRoundtripSentinelError;
+    type SentinelErr = io::Error;
+
+    fn produce_future(
+        &self,
+    ) -> BoxedFuture<Result<Self::ResourceOk, Self::ResourceErr>, Self::SentinelErr> {
+        let (prefix, tag) = (self.probe_prefix(), self.next_probe_tag());
+        let mailbox = self.mailbox.clone();
+        let (threshold, timeout, poll_interval) =
+            (self.threshold, self.timeout, self.poll_interval);
+        // Latency includes submission.
+        let started = Instant::now();
+        Box::new(self.send_probe(&tag).and_then(move |res| {
+            if let Err(e) = res {
+                return Either::A(ok(Err(e)));
+            }
+            debug!("Probe {} sent", tag);
+            Either::B(loop_fn((), move |_| {
+                let mailbox = mailbox.clone();
+                let (prefix, tag) = (prefix.clone(), tag.clone());
+                Delay::new(Instant::now() + poll_interval)
+                    .map_err(io::Error::other)
+                    .and_then(move |_| {
+                        blocking(move || mailbox.take_probe(&prefix, &tag))
+                            .map_err(io::Error::other)
+                    })
+                    .map(move |res| {
+                        let elapsed = started.elapsed();
+                        match res {
+                            Ok(true) if elapsed > threshold => {
+                                Loop::Break(Err(MailRoundtripSentinelError::SlowDelivery {
+                                    elapsed: as_millis(elapsed),
+                                    threshold: as_millis(threshold),
+                                }))
+                            }
+                            Ok(true) => Loop::Break(Ok(elapsed)),
+                            Ok(false) if elapsed > timeout => {
+                                Loop::Break(Err(MailRoundtripSentinelError::NotDelivered {
+                                    timeout: as_millis(timeout),
+                                }))
+                            }
+                            Ok(false) => Loop::Continue(()),
+                            Err(e) => Loop::Break(Err(e)),
+                        }
+                    })
+            }))
+        }))
+    }
+
+    fn compare_errors(&self, left: &Self::ResourceErr, right: &Self::ResourceErr) -> bool {
+        // Delivery time changes on every check, so only kind of failure matters.
+        std::mem::discriminant(left) == std::mem::discriminant(right)
+    }
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+impl ResourceError for MailRoundtripSentinelError {
+    fn description(&self) -> String {
+        format!("{}", self)
+    }
+}
+
+trait ReadWrite: Read + Write + Send {}
+
+impl<T: Read + Write + Send> ReadWrite for T {}
+
+/// Mailbox, where probe emails are delivered.
+#[derive(Clone)]
+struct Mailbox {
+    config: MailboxConfig,
+    tls: Option<TlsConnector>,
+}
+
+impl Mailbox {
+    /// Look for probe with given tag and delete it together with all other probes with tags,
+    /// starting with `prefix` (lost or late ones of previous checks). Returns whether probe
+    /// was found.
+    fn take_probe(&self, prefix: &str, tag: &str) -> Result<bool, MailRoundtripSentinelError> {
+        let mut conn = self.connect()?;
+        match self.config.protocol {
+            MailboxProtocol::Imap => take_probe_imap(&mut conn, &self.config, prefix, tag),
+            MailboxProtocol::Pop3 => take_probe_pop3(&mut conn, &self.config, prefix, tag),
+        }
+        .map_err(MailRoundtripSentinelError::mailbox)
+    }
+
+    fn connect(&self) -> Result<LineConnection, MailRoundtripSentinelError> {
+        let port = match (self.config.port, self.config.protocol, self.tls.is_some()) {
+            (Some(port), _, _) => port,
+            (None, MailboxProtocol::Imap, true) => 993,
+            (None, MailboxProtocol::Imap, false) => 143,
+            (None, MailboxProtocol::Pop3, true) => 995,
+            (None, MailboxProtocol::Pop3, false) => 110,
+        };
+        let addr = (self.config.host.as_str(), port)
+            .to_socket_addrs()
+            .map_err(MailRoundtripSentinelError::mailbox)?
+            .next()
+            .ok_or_else(|| MailRoundtripSentinelError::MailboxError {
+                err: "address resolved to nothing".into(),
+            })?;
+        let timeout = Duration::from_secs(30);
+        let tcp = TcpStream::connect_timeout(&addr, timeout)
+            .and_then(|tcp| {
+                tcp.set_read_timeout(Some(timeout))?;
+                tcp.set_write_timeout(Some(timeout))?;
+                Ok(tcp)
+            })
+            .map_err(MailRoundtripSentinelError::mailbox)?;
+        let stream: Box<dyn ReadWrite> = match self.tls {
+            Some(ref connector) => Box::new(
+                connector
+                    .connect(&self.config.host, tcp)
+                    .map_err(MailRoundtripSentinelError::mailbox)?,
+            ),
+            None => Box::new(tcp),
+        };
+        Ok(LineConnection {
+            inner: BufReader::new(stream),
+        })
+    }
+}
+
+/// Connection for line-based text protocols.
+struct LineConnection {
+    inner: BufReader<Box<dyn ReadWrite>>,
+}
+
+impl LineConnection {
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.inner.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed by server",
+            ));
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let stream = self.inner.get_mut();
+        stream.write_all(line.as_bytes())?;
+        stream.write_all(b"\r\n")?;
+        stream.flush()
+    }
+}
+
+fn protocol_error(msg: String) -> io::Error {
+    io::Error::other(msg)
+}
+
+/// Send tagged IMAP command and return untagged responses.
+fn imap_command(conn: &mut LineConnection, tag: &str, command: &str) -> io::Result<Vec<String>> {
+    conn.write_line(&format!("{} {}", tag, command))?;
+    let mut untagged = Vec::new();
+    loop {
+        let line = conn.read_line()?;
+        if line.starts_with(tag) && line[tag.len()..].starts_with(' ') {
+            let status = &line[tag.len() + 1..];
+            if status.starts_with("OK") {
+                return Ok(untagged);
+            }
+            // Do not leak credentials into error messages.
+            let command = command.split(' ').next().unwrap_or_default();
+            return Err(protocol_error(format!(
+                "IMAP {} failed: {}",
+                command, status
+            )));
+        }
+        untagged.push(line);
+    }
+}
+
+fn imap_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// UIDs of messages with subject, which contains `text`.
+fn imap_search_subject(
+    conn: &mut LineConnection,
+    tag: &str,
+    text: &str,
+) -> io::Result<Vec<String>> {
+    Ok(imap_command(
+        conn,
+        tag,
+        &format!("UID SEARCH SUBJECT {}", imap_quote(text)),
+    )?
+    .iter()
+    .filter_map(|line| line.strip_prefix("* SEARCH"))
+    .flat_map(|x| x.split_whitespace().map(String::from).collect::<Vec<_>>())
+    .collect())
+}
+
+fn take_probe_imap(
+    conn: &mut LineConnection,
+    config: &MailboxConfig,
+    prefix: &str,
+    tag: &str,
+) -> io::Result<bool> {
+    let greeting = conn.read_line()?;
+    if !greeting.starts_with("* OK") {
+        return Err(protocol_error(format!(
+            "unexpected IMAP greeting: {}",
+            greeting
+        )));
+    }
+    imap_command(
+        conn,
+        "a1",
+        &format!(
+            "LOGIN {} {}",
+            imap_quote(&config.login),
+            imap_quote(&config.pwd)
+        ),
+    )?;
+    imap_command(
+        conn,
+        "a2",
+        &format!("SELECT {}", imap_quote(&config.folder)),
+    )?;
+    // Closing bracket keeps tag from matching tags, it is prefix of.
+    let found = !imap_search_subject(conn, "a3", &format!("[{}]", tag))?.is_empty();
+    let uids = imap_search_subject(conn, "a4", &format!("[{}", prefix))?;
+    if !uids.is_empty() {
+        imap_command(
+            conn,
+            "a5",
+            &format!("UID STORE {} +FLAGS.SILENT (\\Deleted)", uids.join(",")),
+        )?;
+        imap_command(conn, "a6", "EXPUNGE")?;
+    }
+    imap_command(conn, "a7", "LOGOUT")?;
+    Ok(found)
+}
+
+fn pop3_command(conn: &mut LineConnection, command: &str) -> io::Result<String> {
+    conn.write_line(command)?;
+    let line = conn.read_line()?;
+    if line.starts_with("+OK") {
+        Ok(line)
+    } else {
+        // Do not leak credentials into error messages.
+        let command = command.split(' ').next().unwrap_or_default();
+        Err(protocol_error(format!("POP3 {} failed: {}", command, line)))
+    }
+}
+
+/// Read rest of multi-line POP3 response.
+fn pop3_read_multiline(conn: &mut LineConnection) -> io::Result<Vec<String>> {
+    let mut lines = Vec::new();
+    loop {
+        let line = conn.read_line()?;
+        if line == "." {
+            return Ok(lines);
+        }
+        lines.push(line.strip_prefix('.').map(String::from).unwrap_or(line));
+    }
+}
+
+/// Words of text in brackets, like tags of probes in subject.
+fn bracketed(text: &str) -> impl Iterator<Item = &str> {
+    text.split('[')
+        .skip(1)
+        .filter_map(|x| x.find(']').map(|end| &x[..end]))
+}
+
+fn take_probe_pop3(
+    conn: &mut LineConnection,
+    config: &MailboxConfig,
+    prefix: &str,
+    tag: &str,
+) -> io::Result<bool> {
+    let greeting = conn.read_line()?;
+    if !greeting.starts_with("+OK") {
+        return Err(protocol_error(format!(
+            "unexpected POP3 greeting: {}",
+            greeting
+        )));
+    }
+    pop3_command(conn, &format!("USER {}", config.login))?;
+    pop3_command(conn, &format!("PASS {}", config.pwd))?;
+    pop3_command(conn, "LIST")?;
+    let ids = pop3_read_multiline(conn)?
+        .iter()
+        .filter_map(|line| line.split_whitespace().next().map(String::from))
+        .collect::<Vec<_>>();
+    let mut found = false;
+    for id in ids {
+        pop3_command(conn, &format!("TOP {} 0", id))?;
+        let headers = pop3_read_multiline(conn)?;
+        let tags = headers
+            .iter()
+            .flat_map(|x| bracketed(x))
+            .collect::<Vec<_>>();
+        if tags.iter().any(|x| x.starts_with(prefix)) {
+            pop3_command(conn, &format!("DELE {}", id))?;
+            found |= tags.contains(&tag);
+        }
+    }
+    // Deletions are committed only on QUIT.
+    pop3_command(conn, "QUIT")?;
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Cursor,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    /// Server, which replies with scripted lines and records commands.
+    struct ScriptedServer {
+        replies: Cursor<Vec<u8>>,
+        commands: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Read for ScriptedServer {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.replies.read(buf)
+        }
+    }
+
+    impl Write for ScriptedServer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.commands.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn connection(replies: &[&str]) -> (LineConnection, Arc<Mutex<Vec<u8>>>) {
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let server = ScriptedServer {
+            replies: Cursor::new(format!("{}\r\n", replies.join("\r\n")).into_bytes()),
+            commands: commands.clone(),
+        };
+        let conn = LineConnection {
+            inner: BufReader::new(Box::new(server)),
+        };
+        (conn, commands)
+    }
+
+    fn commands(commands: &Arc<Mutex<Vec<u8>>>) -> Vec<String> {
+        String::from_utf8(commands.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    fn mailbox_config(protocol: &str) -> MailboxConfig {
+        serde_yaml::from_str(&format!(
+            "{{protocol: {}, host: mail.local, login: probe, pwd: secret}}",
+            protocol
+        ))
+        .unwrap()
+    }
+
+    const PREFIX: &str = "sentinel-probe-db-";
+
+    /// Replies to POP3 session with messages of given subjects, which are all probes.
+    fn pop3_replies(subjects: &[&str]) -> Vec<String> {
+        // Greeting and replies to USER, PASS and LIST.
+        let mut replies = vec![
+            "+OK POP3 ready".to_string(),
+            "+OK".into(),
+            "+OK".into(),
+            "+OK".into(),
+        ];
+        replies.extend((1..=subjects.len()).map(|i| format!("{} 100", i)));
+        replies.push(".".into());
+        for subject in subjects {
+            replies.push("+OK".into());
+            replies.push(format!("Subject: {}", subject));
+            replies.push(".".into());
+            if subject.contains(PREFIX) {
+                replies.push("+OK".into());
+            }
+        }
+        replies.push("+OK bye".into());
+        replies
+    }
+
+    #[test]
+    fn bracketed_words_are_found() {
+        let words = bracketed("Probe [sentinel-probe-db-1] [x] [unterminated").collect::<Vec<_>>();
+        assert_eq!(words, vec!["sentinel-probe-db-1", "x"]);
+        assert_eq!(bracketed("no brackets").count(), 0);
+    }
+
+    #[test]
+    fn pop3_probe_tag_matches_exactly() {
+        let replies = pop3_replies(&[
+            "Sentinel mail round-trip probe [sentinel-probe-db-100-12]",
+            "Hello",
+        ]);
+        let (mut conn, sent) = connection(&replies.iter().map(|x| x.as_str()).collect::<Vec<_>>());
+        let found = take_probe_pop3(
+            &mut conn,
+            &mailbox_config("pop3"),
+            PREFIX,
+            "sentinel-probe-db-100-1",
+        )
+        .unwrap();
+        assert!(!found);
+        // Late probe of previous check is deleted anyway, other mail is kept.
+        let sent = commands(&sent);
+        assert!(sent.contains(&"DELE 1".to_string()));
+        assert!(!sent.contains(&"DELE 2".to_string()));
+    }
+
+    #[test]
+    fn pop3_probe_is_found() {
+        let replies = pop3_replies(&[
+            "Sentinel mail round-trip probe [sentinel-probe-db-100-12]",
+            "Sentinel mail round-trip probe [sentinel-probe-db-100-1]",
+        ]);
+        let (mut conn, sent) = connection(&replies.iter().map(|x| x.as_str()).collect::<Vec<_>>());
+        let found = take_probe_pop3(
+            &mut conn,
+            &mailbox_config("pop3"),
+            PREFIX,
+            "sentinel-probe-db-100-1",
+        )
+        .unwrap();
+        assert!(found);
+        let sent = commands(&sent);
+        assert!(sent.contains(&"DELE 1".to_string()));
+        assert!(sent.contains(&"DELE 2".to_string()));
+    }
+
+    #[test]
+    fn imap_searches_delimited_tag() {
+        let (mut conn, sent) = connection(&[
+            "* OK IMAP ready",
+            "a1 OK",
+            "a2 OK",
+            "* SEARCH",
+            "a3 OK",
+            "* SEARCH 7",
+            "a4 OK",
+            "a5 OK",
+            "a6 OK",
+            "a7 OK",
+        ]);
+        let found = take_probe_imap(
+            &mut conn,
+            &mailbox_config("imap"),
+            PREFIX,
+            "sentinel-probe-db-100-1",
+        )
+        .unwrap();
+        assert!(!found);
+        let sent = commands(&sent);
+        assert_eq!(
+            sent[2],
+            "a3 UID SEARCH SUBJECT \"[sentinel-probe-db-100-1]\""
+        );
+        assert_eq!(sent[3], "a4 UID SEARCH SUBJECT \"[sentinel-probe-db-\"");
+        assert_eq!(sent[4], "a5 UID STORE 7 +FLAGS.SILENT (\\Deleted)");
+    }
+}
@@ -1 +1,5 @@
+pub(crate) mod grpc;
 pub(crate) mod http;
+pub(crate) mod mail_roundtrip;
+pub(crate) mod ntp;
+pub(crate) mod websocket;
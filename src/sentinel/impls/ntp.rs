@@ -0,0 +1,489 @@
+use std::{
+    error::Error,
+    fmt,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{future::join_all, Future};
+
+use tokio::net::UdpSocket;
+use tokio_timer::Timeout;
+
+use log::debug;
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{
+    blocking,
+    sentinel::{Config, ResourceError, Sentinel, SentinelImpl},
+    BoxedFuture, BoxedStream,
+};
+
+const NTP_PORT: u16 = 123;
+const NTP_PACKET_SIZE: usize = 48;
+/// Seconds between NTP (1900) and Unix (1970) epochs.
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+const LEAP_INDICATOR_ALARM: u8 = 3;
+const STRATUM_UNSYNCHRONIZED: u8 = 16;
+
+#[derive(Debug, Fail)]
+pub(crate) enum NtpSentinelError {
+    // Resource failures
+    #[fail(
+        display = "Clock offset {} ms exceeds threshold {} ms",
+        offset, max_offset
+    )]
+    OffsetTooLarge { offset: i64, max_offset: u64 },
+    #[fail(
+        display = "Server is unsynchronised (stratum {}, leap indicator {})",
+        stratum, leap_indicator
+    )]
+    Unsynchronized { stratum: u8, leap_indicator: u8 },
+    #[fail(display = "Transport error: {}", err)]
+    TransportError { err: String },
+    #[fail(display = "Invalid response: {}", reason)]
+    InvalidResponse { reason: String },
+    #[fail(display = "Query timed out")]
+    Timeout,
+    #[fail(display = "{} of {} servers failed:{}", failed, total, servers)]
+    ServersFailed {
+        failed: usize,
+        total: usize,
+        servers: NtpServerStatuses,
+    },
+
+    // Build failures
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserializeError { err: serde_yaml::Error },
+    #[fail(display = "No servers configured")]
+    NoServers,
+}
+
+impl NtpSentinelError {
+    fn transport<E: Error>(err: E) -> Self {
+        NtpSentinelError::TransportError {
+            err: format!("{}", err),
+        }
+    }
+
+    fn invalid_response<S: Into<String>>(reason: S) -> Self {
+        NtpSentinelError::InvalidResponse {
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Result of query to every server, used in error reports.
+#[derive(Debug)]
+pub(crate) struct NtpServerStatuses(Vec<(String, Result<i64, NtpSentinelError>)>);
+
+impl NtpServerStatuses {
+    fn failed(&self) -> impl Iterator<Item = (&String, &NtpSentinelError)> {
+        self.0
+            .iter()
+            .filter_map(|(server, res)| res.as_ref().err().map(|e| (server, e)))
+    }
+}
+
+impl fmt::Display for NtpServerStatuses {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (server, res) in self.0.iter() {
+            match res {
+                Ok(offset) => write!(f, "\n  {}: OK (offset {} ms)", server, offset)?,
+                Err(e) => write!(f, "\n  {}: FAILED ({})", server, e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct NtpSentinelConfig {
+    /// Servers as `host`, `host:port`, IP address or `[IPv6]:port`.
+    servers: Vec<String>,
+    /// Maximum allowed clock offset in milliseconds.
+    max_offset: u64,
+    /// Timeout of single query in milliseconds.
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+}
+
+fn default_timeout() -> u64 {
+    5000
+}
+
+pub(crate) struct NtpSentinel {
+    servers: Vec<String>,
+    max_offset: u64,
+    timeout: Duration,
+}
+
+impl NtpSentinel {
+    pub(crate) fn create_sentinel_stream(
+        config: Config,
+    ) -> Result<BoxedStream<(), Box<dyn Error + Send>>, Box<dyn Fail>> {
+        let ntp_config: NtpSentinelConfig =
+            serde_yaml::from_value(config.config.clone()).map_err(|e| {
+                Box::new(NtpSentinelError::YamlDeserializeError { err: e }) as Box<dyn Fail>
+            })?;
+        if ntp_config.servers.is_empty() {
+            return Err(Box::new(NtpSentinelError::NoServers) as Box<dyn Fail>);
+        }
+        let sentinel_impl = Box::new(Self {
+            servers: ntp_config.servers,
+            max_offset: ntp_config.max_offset,
+            timeout: Duration::from_millis(ntp_config.timeout),
+        });
+
+        let sent = Sentinel::new(sentinel_impl, config);
+        Ok(Box::new(sent))
+    }
+
+    /// Query single server and return clock offset in milliseconds.
+    fn check_server(
+        &self,
+        server: String,
+    ) -> impl Future<Item = (String, Result<i64, NtpSentinelError>), Error = tokio_timer::Error>
+    {
+        let max_offset = self.max_offset;
+        let address = server_address(&server);
+        let query = blocking(move || address.to_socket_addrs().map(|mut x| x.next()))
+            .map_err(NtpSentinelError::transport)
+            .and_then(|res| match res {
+                Ok(Some(addr)) => Ok(addr),
+                Ok(None) => Err(NtpSentinelError::TransportError {
+                    err: "address resolved to nothing".into(),
+                }),
+                Err(e) => Err(NtpSentinelError::transport(e)),
+            })
+            .and_then(query_server);
+        Timeout::new(query, self.timeout).then(move |res| {
+            let res = match res {
+                Ok(sample) => sample.check(max_offset),
+                Err(e) if e.is_elapsed() => Err(NtpSentinelError::Timeout),
+                Err(e) if e.is_inner() => Err(e.into_inner().unwrap()),
+                Err(e) => return Err(e.into_timer().unwrap()),
+            };
+            debug!("NTP server {}: {:?}", server, res);
+            Ok((server, res))
+        })
+    }
+}
+
+impl SentinelImpl for NtpSentinel {
+    type ResourceOk = Vec<i64>;
+    type ResourceErr = NtpSentinelError;
+    type SentinelErr = tokio_timer::Error;
+
+    fn produce_future(
+        &self,
+    ) -> BoxedFuture<Result<Self::ResourceOk, Self::ResourceErr>, Self::SentinelErr> {
+        let checks = self
+            .servers
+            .iter()
+            .map(|server| self.check_server(server.clone()))
+            .collect::<Vec<_>>();
+        Box::new(join_all(checks).map(|mut statuses| {
+            let total = statuses.len();
+            // Single server reports its own error.
+            if total == 1 {
+                return statuses.pop().unwrap().1.map(|offset| vec![offset]);
+            }
+            let statuses = NtpServerStatuses(statuses);
+            let failed = statuses.failed().count();
+            if failed > 0 {
+                Err(NtpSentinelError::ServersFailed {
+                    failed,
+                    total,
+                    servers: statuses,
+                })
+            } else {
+                Ok(statuses
+                    .0
+                    .into_iter()
+                    .filter_map(|(_, res)| res.ok())
+                    .collect())
+            }
+        }))
+    }
+
+    fn compare_errors(&self, left: &Self::ResourceErr, right: &Self::ResourceErr) -> bool {
+        compare_errors(left, right)
+    }
+}
+
+fn compare_errors(left: &NtpSentinelError, right: &NtpSentinelError) -> bool {
+    match (left, right) {
+        // Same set of failed servers, each failed the same way.
+        (
+            NtpSentinelError::ServersFailed { servers: l, .. },
+            NtpSentinelError::ServersFailed { servers: r, .. },
+        ) => {
+            l.failed().count() == r.failed().count()
+                && l.failed()
+                    .zip(r.failed())
+                    .all(|((ls, le), (rs, re))| ls == rs && compare_errors(le, re))
+        }
+        // Offset differs on every check, so only kind of failure matters.
+        (l, r) => std::mem::discriminant(l) == std::mem::discriminant(r),
+    }
+}
+
+impl ResourceError for NtpSentinelError {
+    fn description(&self) -> String {
+        format!("{}", self)
+    }
+}
+
+/// Timestamps of single SNTP exchange, as described in RFC 4330.
+#[derive(Debug)]
+struct NtpSample {
+    leap_indicator: u8,
+    stratum: u8,
+    /// Client transmit time.
+    t1: f64,
+    /// Server receive time.
+    t2: f64,
+    /// Server transmit time.
+    t3: f64,
+    /// Client receive time.
+    t4: f64,
+}
+
+impl NtpSample {
+    /// Offset of local clock relative to server in milliseconds.
+    fn offset(&self) -> i64 {
+        (((self.t2 - self.t1) + (self.t3 - self.t4)) / 2.0 * 1000.0).round() as i64
+    }
+
+    fn check(&self, max_offset: u64) -> Result<i64, NtpSentinelError> {
+        if self.leap_indicator == LEAP_INDICATOR_ALARM
+            || self.stratum == 0
+            || self.stratum >= STRATUM_UNSYNCHRONIZED
+        {
+            return Err(NtpSentinelError::Unsynchronized {
+                stratum: self.stratum,
+                leap_indicator: self.leap_indicator,
+            });
+        }
+        let offset = self.offset();
+        if offset.unsigned_abs() > max_offset {
+            return Err(NtpSentinelError::OffsetTooLarge { offset, max_offset });
+        }
+        Ok(offset)
+    }
+}
+
+/// Address of server in form, which can be resolved, with default port, if it has none.
+fn server_address(server: &str) -> String {
+    if server.parse::<SocketAddr>().is_ok() {
+        return server.into();
+    }
+    let host = server.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return SocketAddr::new(ip, NTP_PORT).to_string();
+    }
+    if server.contains(':') {
+        server.into()
+    } else {
+        format!("{}:{}", server, NTP_PORT)
+    }
+}
+
+fn query_server(addr: SocketAddr) -> impl Future<Item = NtpSample, Error = NtpSentinelError> {
+    let bind_addr = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    }
+    .parse()
+    .unwrap();
+    let mut request = [0u8; NTP_PACKET_SIZE];
+    // Leap indicator 0, version 4, mode 3 (client).
+    request[0] = 0x23;
+    let t1 = now_ntp();
+    request[40..48].copy_from_slice(&t1.to_be_bytes());
+    futures::future::result(UdpSocket::bind(&bind_addr))
+        .and_then(move |socket| socket.send_dgram(request, &addr))
+        .and_then(|(socket, _)| socket.recv_dgram(vec![0u8; 1024]))
+        .map_err(NtpSentinelError::transport)
+        .and_then(move |(_, data, len, _)| parse_response(&data[..len], t1, now_ntp()))
+}
+
+fn parse_response(data: &[u8], t1: u64, t4: u64) -> Result<NtpSample, NtpSentinelError> {
+    if data.len() < NTP_PACKET_SIZE {
+        return Err(NtpSentinelError::invalid_response("packet too short"));
+    }
+    let timestamp = |offset: usize| {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&data[offset..offset + 8]);
+        u64::from_be_bytes(bytes)
+    };
+    let mode = data[0] & 0x7;
+    if mode != 4 {
+        return Err(NtpSentinelError::invalid_response(format!(
+            "unexpected mode {}",
+            mode
+        )));
+    }
+    if timestamp(24) != t1 {
+        return Err(NtpSentinelError::invalid_response(
+            "originate timestamp does not match request",
+        ));
+    }
+    Ok(NtpSample {
+        leap_indicator: data[0] >> 6,
+        stratum: data[1],
+        t1: ntp_to_secs(t1),
+        t2: ntp_to_secs(timestamp(32)),
+        t3: ntp_to_secs(timestamp(40)),
+        t4: ntp_to_secs(t4),
+    })
+}
+
+/// Current time as 64-bit NTP timestamp (32.32 fixed point seconds since 1900).
+fn now_ntp() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs() + NTP_UNIX_OFFSET;
+    let frac = (u64::from(now.subsec_nanos()) << 32) / 1_000_000_000;
+    (secs << 32) | frac
+}
+
+fn ntp_to_secs(timestamp: u64) -> f64 {
+    (timestamp >> 32) as f64 + (timestamp & 0xffff_ffff) as f64 / 4_294_967_296.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net, thread};
+
+    use super::*;
+
+    /// NTP timestamp of whole seconds.
+    fn secs(x: u64) -> u64 {
+        x << 32
+    }
+
+    fn response(leap_indicator: u8, stratum: u8, t1: u64, t2: u64, t3: u64) -> Vec<u8> {
+        let mut data = vec![0u8; NTP_PACKET_SIZE];
+        // Version 4, mode 4 (server).
+        data[0] = (leap_indicator << 6) | 0x24;
+        data[1] = stratum;
+        data[24..32].copy_from_slice(&t1.to_be_bytes());
+        data[32..40].copy_from_slice(&t2.to_be_bytes());
+        data[40..48].copy_from_slice(&t3.to_be_bytes());
+        data
+    }
+
+    fn check(data: &[u8]) -> Result<i64, NtpSentinelError> {
+        parse_response(data, secs(1000), secs(1000))?.check(1000)
+    }
+
+    #[test]
+    fn response_is_validated() {
+        let data = response(0, 2, secs(1000), secs(1000), secs(1000));
+        assert!(matches!(
+            check(&data[..NTP_PACKET_SIZE - 1]),
+            Err(NtpSentinelError::InvalidResponse { .. })
+        ));
+        let mut client = data.clone();
+        client[0] = 0x23;
+        assert!(matches!(
+            check(&client),
+            Err(NtpSentinelError::InvalidResponse { .. })
+        ));
+        assert!(matches!(
+            parse_response(&data, secs(999), secs(1000)),
+            Err(NtpSentinelError::InvalidResponse { .. })
+        ));
+        assert_eq!(check(&data).unwrap(), 0);
+    }
+
+    #[test]
+    fn unsynchronized_server_fails() {
+        for &(leap_indicator, stratum) in &[(0, 0), (0, 16), (3, 2)] {
+            let data = response(leap_indicator, stratum, secs(1000), secs(1000), secs(1000));
+            match check(&data) {
+                Err(NtpSentinelError::Unsynchronized {
+                    stratum: s,
+                    leap_indicator: l,
+                }) => assert_eq!((l, s), (leap_indicator, stratum)),
+                res => panic!("unexpected result {:?}", res),
+            }
+        }
+        let data = response(0, 15, secs(1000), secs(1000), secs(1000));
+        assert!(check(&data).is_ok());
+    }
+
+    #[test]
+    fn offset_is_positive_when_server_is_ahead() {
+        let ahead = response(0, 2, secs(1000), secs(1002), secs(1002));
+        assert_eq!(
+            parse_response(&ahead, secs(1000), secs(1000))
+                .unwrap()
+                .offset(),
+            2000
+        );
+        let behind = response(0, 2, secs(1000), secs(998), secs(998));
+        assert_eq!(
+            parse_response(&behind, secs(1000), secs(1000))
+                .unwrap()
+                .offset(),
+            -2000
+        );
+        // Round trip delay doesn't count.
+        let delayed = response(0, 2, secs(1000), secs(1001), secs(1001));
+        assert_eq!(
+            parse_response(&delayed, secs(1000), secs(1002))
+                .unwrap()
+                .offset(),
+            0
+        );
+        assert!(matches!(
+            check(&ahead),
+            Err(NtpSentinelError::OffsetTooLarge {
+                offset: 2000,
+                max_offset: 1000
+            })
+        ));
+    }
+
+    #[test]
+    fn server_address_has_default_port() {
+        assert_eq!(server_address("pool.ntp.org"), "pool.ntp.org:123");
+        assert_eq!(server_address("pool.ntp.org:1123"), "pool.ntp.org:1123");
+        assert_eq!(server_address("10.0.0.1"), "10.0.0.1:123");
+        assert_eq!(server_address("10.0.0.1:1123"), "10.0.0.1:1123");
+        assert_eq!(server_address("::1"), "[::1]:123");
+        assert_eq!(server_address("fe80::1"), "[fe80::1]:123");
+        assert_eq!(server_address("[::1]"), "[::1]:123");
+        assert_eq!(server_address("[::1]:1123"), "[::1]:1123");
+    }
+
+    #[test]
+    fn query_local_server() {
+        let server = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut request = [0u8; NTP_PACKET_SIZE];
+            let (_, client) = server.recv_from(&mut request).unwrap();
+            let mut t1 = [0u8; 8];
+            t1.copy_from_slice(&request[40..48]);
+            let t1 = u64::from_be_bytes(t1);
+            // Server is 5 seconds ahead.
+            let now = t1 + secs(5);
+            server
+                .send_to(&response(0, 2, t1, now, now), client)
+                .unwrap();
+        });
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let sample = runtime.block_on(query_server(addr)).unwrap();
+        assert_eq!(sample.stratum, 2);
+        assert!((sample.offset() - 5000).abs() < 100, "{}", sample.offset());
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    error::Error,
+    io,
+    net::{TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use reqwest::Url;
+
+use native_tls::{TlsConnector, TlsStream};
+
+use tungstenite::{handshake::HandshakeError, stream::Stream, Message, WebSocket};
+
+use log::debug;
+
+use serde::Deserialize;
+
+use failure::Fail;
+
+use crate::{
+    blocking,
+    sentinel::{Config, ResourceError, Sentinel, SentinelImpl},
+    BoxedFuture, BoxedStream,
+};
+
+type WsStream = Stream<TcpStream, TlsStream<TcpStream>>;
+
+#[derive(Debug, Fail)]
+pub(crate) enum WebSocketSentinelError {
+    // Resource failures
+    #[fail(display = "Connection error: {}", err)]
+    ConnectionError { err: String },
+    #[fail(display = "Handshake rejected with HTTP code: {}", code)]
+    HandshakeRejected { code: u16 },
+    #[fail(display = "Handshake failed: {}", err)]
+    HandshakeFailed { err: String },
+    #[fail(display = "Handshake timed out")]
+    HandshakeTimeout,
+    #[fail(display = "No matching reply within timeout")]
+    NoReply,
+    #[fail(display = "Connection closed before matching reply")]
+    ClosedBeforeReply,
+
+    // Build failures
+    #[fail(display = "YAML deserialize error: {}", err)]
+    YamlDeserializeError { err: serde_yaml::Error },
+    #[fail(display = "Url parse error: {}", err)]
+    UrlParseError { err: reqwest::UrlError },
+    #[fail(display = "Unsupported URL scheme: {}", scheme)]
+    UnsupportedScheme { scheme: String },
+    #[fail(display = "TLS connector error: {}", err)]
+    TlsConnectorError { err: native_tls::Error },
+}
+
+impl WebSocketSentinelError {
+    fn connection<E: Error>(err: E) -> Self {
+        WebSocketSentinelError::ConnectionError {
+            err: format!("{}", err),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+struct WebSocketSentinelConfig {
+    /// `ws://` or `wss://` URL.
+    url: String,
+    /// Text message sent after handshake.
+    send: Option<String>,
+    /// Substring expected in reply. Without it any data frame is a reply.
+    expect: Option<String>,
+    /// Timeout of whole check in milliseconds.
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+}
+
+fn default_timeout() -> u64 {
+    5000
+}
+
+#[derive(Clone)]
+struct WebSocketCheck {
+    url: Url,
+    tls: Option<TlsConnector>,
+    send: Option<String>,
+    expect: Option<String>,
+    timeout: Duration,
+}
+
+impl WebSocketCheck {
+    fn run(&self) -> Result<(), WebSocketSentinelError> {
+        let deadline = Instant::now() + self.timeout;
+        let mut socket = self.handshake(deadline)?;
+        if let Some(ref msg) = self.send {
+            socket
+                .write_message(Message::Text(msg.clone()))
+                .map_err(WebSocketSentinelError::connection)?;
+        }
+        if self.send.is_some() || self.expect.is_some() {
+            self.wait_reply(&mut socket, deadline)?;
+        }
+        let _ = socket.close(None);
+        let _ = socket.write_pending();
+        Ok(())
+    }
+
+    fn handshake(&self, deadline: Instant) -> Result<WebSocket<WsStream>, WebSocketSentinelError> {
+        let host = self.url.host_str().unwrap_or_default();
+        let port = self.url.port_or_known_default().unwrap_or(80);
+        let addr = (host, port)
+            .to_socket_addrs()
+            .map_err(WebSocketSentinelError::connection)?
+            .next()
+            .ok_or_else(|| WebSocketSentinelError::ConnectionError {
+                err: "address resolved to nothing".into(),
+            })?;
+        let timeout = remaining(deadline).ok_or(WebSocketSentinelError::HandshakeTimeout)?;
+        let tcp = TcpStream::connect_timeout(&addr, timeout)
+            .map_err(WebSocketSentinelError::connection)?;
+        set_timeouts(&tcp, timeout)?;
+        let stream = match self.tls {
+            Some(ref connector) => Stream::Tls(
+                connector
+                    .connect(host, tcp)
+                    .map_err(WebSocketSentinelError::connection)?,
+            ),
+            None => Stream::Plain(tcp),
+        };
+        match tungstenite::client(self.url.as_str(), stream) {
+            Ok((socket, _)) => Ok(socket),
+            Err(HandshakeError::Interrupted(_)) => Err(WebSocketSentinelError::HandshakeTimeout),
+            Err(HandshakeError::Failure(tungstenite::Error::Http(code))) => {
+                Err(WebSocketSentinelError::HandshakeRejected {
+                    code: code.as_u16(),
+                })
+            }
+            Err(HandshakeError::Failure(tungstenite::Error::Io(ref e))) if is_timeout(e) => {
+                Err(WebSocketSentinelError::HandshakeTimeout)
+            }
+            Err(HandshakeError::Failure(e)) => Err(WebSocketSentinelError::HandshakeFailed {
+                err: format!("{}", e),
+            }),
+        }
+    }
+
+    fn wait_reply(
+        &self,
+        socket: &mut WebSocket<WsStream>,
+        deadline: Instant,
+    ) -> Result<(), WebSocketSentinelError> {
+        loop {
+            let timeout = remaining(deadline).ok_or(WebSocketSentinelError::NoReply)?;
+            match socket.get_ref() {
+                Stream::Plain(tcp) => set_timeouts(tcp, timeout)?,
+                Stream::Tls(tls) => set_timeouts(tls.get_ref(), timeout)?,
+            }
+            let text = match socket.read_message() {
+                Ok(Message::Text(text)) => text,
+                Ok(Message::Binary(data)) => String::from_utf8_lossy(&data).into_owned(),
+                Ok(Message::Ping(_)) | Ok(Message::Pong(_)) => continue,
+                Ok(Message::Close(_))
+                | Err(tungstenite::Error::ConnectionClosed)
+                | Err(tungstenite::Error::AlreadyClosed) => {
+                    return Err(WebSocketSentinelError::ClosedBeforeReply)
+                }
+                Err(tungstenite::Error::Io(ref e)) if is_timeout(e) => {
+                    return Err(WebSocketSentinelError::NoReply)
+                }
+                Err(e) => return Err(WebSocketSentinelError::connection(e)),
+            };
+            debug!("WebSocket {} reply: {}", self.url, text);
+            match self.expect {
+                Some(ref expect) if !text.contains(expect.as_str()) => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+fn remaining(deadline: Instant) -> Option<Duration> {
+    let now = Instant::now();
+    if now >= deadline {
+        None
+    } else {
+        Some(deadline - now)
+    }
+}
+
+fn set_timeouts(tcp: &TcpStream, timeout: Duration) -> Result<(), WebSocketSentinelError> {
+    let timeout = Some(timeout);
+    tcp.set_read_timeout(timeout)
+        .and_then(|_| tcp.set_write_timeout(timeout))
+        .map_err(WebSocketSentinelError::connection)
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+pub(crate) struct WebSocketSentinel {
+    check: WebSocketCheck,
+}
+
+impl WebSocketSentinel {
+    pub(crate) fn create_sentinel_stream(
+        config: Config,
+    ) -> Result<BoxedStream<(), Box<dyn Error + Send>>, Box<dyn Fail>> {
+        let ws_config: WebSocketSentinelConfig = serde_yaml::from_value(config.config.clone())
+            .map_err(|e| {
+                Box::new(WebSocketSentinelError::YamlDeserializeError { err: e }) as Box<dyn Fail>
+            })?;
+        let url = Url::parse(&ws_config.url).map_err(|e| {
+            Box::new(WebSocketSentinelError::UrlParseError { err: e }) as Box<dyn Fail>
+        })?;
+        let tls = match url.scheme() {
+            "ws" => None,
+            "wss" => Some(TlsConnector::new().map_err(|e| {
+                Box::new(WebSocketSentinelError::TlsConnectorError { err: e }) as Box<dyn Fail>
+            })?),
+            scheme => {
+                return Err(Box::new(WebSocketSentinelError::UnsupportedScheme {
+                    scheme: scheme.into(),
+                }) as Box<dyn Fail>)
+            }
+        };
+        let sentinel_impl = Box::new(Self {
+            check: WebSocketCheck {
+                url,
+                tls,
+                send: ws_config.send,
+                expect: ws_config.expect,
+                timeout: Duration::from_millis(ws_config.timeout),
+            },
+        });
+
+        let sent = Sentinel::new(sentinel_impl, config);
+        Ok(Box::new(sent))
+    }
+}
+
+impl SentinelImpl for WebSocketSentinel {
+    type ResourceOk = ();
+    type ResourceErr = WebSocketSentinelError;
+    type SentinelErr = tokio_threadpool::BlockingError;
+
+    fn produce_future(
+        &self,
+    ) -> BoxedFuture<Result<Self::ResourceOk, Self::ResourceErr>, Self::SentinelErr> {
+        let check = self.check.clone();
+        Box::new(blocking(move || check.run()))
+    }
+
+    fn compare_errors(&self, left: &Self::ResourceErr, right: &Self::ResourceErr) -> bool {
+        match (left, right) {
+            (
+                WebSocketSentinelError::HandshakeRejected { code: l },
+                WebSocketSentinelError::HandshakeRejected { code: r },
+            ) => l == r,
+            (l, r) => std::mem::discriminant(l) == std::mem::discriminant(r),
+        }
+    }
+}
+
+impl ResourceError for WebSocketSentinelError {
+    fn description(&self) -> String {
+        format!("{}", self)
+    }
+}
@@ -1,20 +1,32 @@
-use std::{error::Error, time::Duration};
+use std::{
+    collections::VecDeque,
+    error::Error,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use futures::{Async, Future, Poll, Stream};
 
+use chrono::{DateTime, Utc};
 use either::Either;
 use tokio_timer::{sleep, Delay};
 
+use log::{error, info};
+
 use serde::Deserialize;
 
 use crate::{
-    notifier::{Message, NotifierSender},
+    ack::Acks,
+    notifier::{CheckResult, Message, MessageKind, NotifierSender, ReminderConfig, ResourceInfo},
     BoxedFuture,
 };
 
 mod impls;
 pub(crate) use impls::*;
 
+/// Number of recent check results attached to notifications.
+const HISTORY_LEN: usize = 10;
+
 #[derive(Clone, Debug, Deserialize)]
 pub(crate) struct YamlConfig {
     pub interval: u64,
@@ -22,6 +34,12 @@ pub(crate) struct YamlConfig {
     #[serde(rename = "type")]
     pub type_: String,
     pub notifiers: Vec<String>,
+    #[serde(flatten)]
+    pub info: ResourceInfo,
+    #[serde(flatten)]
+    pub reminder: ReminderConfig,
+    /// Name of escalation policy.
+    pub escalation: Option<String>,
     pub config: serde_yaml::Value,
 }
 
@@ -30,9 +48,20 @@ pub(crate) struct Config {
     pub name: String,
     pub type_: String,
     pub notifiers: Vec<Box<dyn NotifierSender>>,
+    pub info: ResourceInfo,
+    pub reminder: ReminderConfig,
+    pub escalation: Vec<Tier>,
+    pub acks: Arc<Acks>,
     pub config: serde_yaml::Value,
 }
 
+/// Notifiers of escalation policy, which are notified, when error stays active and isn't
+/// acknowledged for `after`.
+pub(crate) struct Tier {
+    pub after: chrono::Duration,
+    pub notifiers: Vec<Box<dyn NotifierSender>>,
+}
+
 trait ResourceError {
     fn description(&self) -> String;
 }
@@ -44,7 +73,7 @@ enum ResourceErrorState<'a, E: ResourceError> {
 }
 
 impl<'a, E: ResourceError> ResourceErrorState<'a, E> {
-    fn create_message(&self, resource_name: &str) -> Message {
+    fn create_message(&self, resource_name: &str, since: DateTime<Utc>) -> Message {
         match self {
             ResourceErrorState::New(e) => {
                 let title = format!("Error (new) {}", resource_name);
@@ -53,7 +82,14 @@ impl<'a, E: ResourceError> ResourceErrorState<'a, E> {
                     resource_name,
                     e.description()
                 );
-                Message::new(title, body)
+                Message::new(
+                    title,
+                    body,
+                    MessageKind::New,
+                    resource_name.into(),
+                    e.description(),
+                    since,
+                )
             }
             ResourceErrorState::Changed(e1, e2) => {
                 let title = format!("Error (changed) {}", resource_name);
@@ -63,7 +99,14 @@ impl<'a, E: ResourceError> ResourceErrorState<'a, E> {
                     e1.description(),
                     e2.description()
                 );
-                Message::new(title, body)
+                Message::new(
+                    title,
+                    body,
+                    MessageKind::Changed,
+                    resource_name.into(),
+                    e2.description(),
+                    since,
+                )
             }
             ResourceErrorState::Resolved(e) => {
                 let title = format!("Error (resolved) {}", resource_name);
@@ -72,7 +115,14 @@ impl<'a, E: ResourceError> ResourceErrorState<'a, E> {
                     resource_name,
                     e.description()
                 );
-                Message::new(title, body)
+                Message::new(
+                    title,
+                    body,
+                    MessageKind::Resolved,
+                    resource_name.into(),
+                    e.description(),
+                    since,
+                )
             }
         }
     }
@@ -95,9 +145,32 @@ struct Sentinel<R, E: ResourceError, C> {
     inner: Either<BoxedFuture<Result<R, E>, C>, Delay>,
     sentinel_impl: Box<dyn SentinelImpl<ResourceOk = R, ResourceErr = E, SentinelErr = C>>,
     active_error: Option<E>,
+    /// When active error was first observed.
+    active_error_since: DateTime<Utc>,
     interval: Duration,
     notifiers: Vec<Box<dyn NotifierSender>>,
     resource_name: String,
+    info: ResourceInfo,
+    /// Recent check results, oldest first.
+    history: VecDeque<CheckResult>,
+    /// Interval between reminders about active error.
+    remind_every: Option<chrono::Duration>,
+    max_reminders: Option<u32>,
+    last_notification: Option<LastNotification>,
+    escalation: Vec<Tier>,
+    /// Number of tiers, notified about active error.
+    escalated: usize,
+    /// Fires, when the next tier is due.
+    escalation_timer: Option<Delay>,
+    acks: Arc<Acks>,
+}
+
+/// Last notification about active error.
+struct LastNotification {
+    msg: Message,
+    at: DateTime<Utc>,
+    /// Number of reminders since error was reported.
+    reminders: u32,
 }
 
 impl<R, E: ResourceError, C: Error + Send + 'static> Stream for Sentinel<R, E, C> {
@@ -105,6 +178,7 @@ impl<R, E: ResourceError, C: Error + Send + 'static> Stream for Sentinel<R, E, C
     type Error = Box<dyn Error + Send>;
 
     fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        self.poll_escalation();
         loop {
             match self.inner {
                 Either::Left(ref mut fut) => {
@@ -134,27 +208,68 @@ impl<R, E: ResourceError, C: Error + Send + 'static> Stream for Sentinel<R, E, C
 impl<R, E: ResourceError, C: Error + Send + 'static> Sentinel<R, E, C> {
     pub(crate) fn new(
         sentinel_impl: Box<dyn SentinelImpl<ResourceOk = R, ResourceErr = E, SentinelErr = C>>,
-        interval: u64,
-        notifiers: Vec<Box<dyn NotifierSender>>,
-        resource_name: String,
+        config: Config,
     ) -> Self {
         Self {
             inner: Either::Left(sentinel_impl.produce_future()),
             sentinel_impl,
             active_error: None,
-            interval: Duration::from_millis(interval),
-            notifiers,
-            resource_name,
+            active_error_since: Utc::now(),
+            interval: Duration::from_millis(config.interval),
+            notifiers: config.notifiers,
+            resource_name: config.name,
+            info: config.info,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            remind_every: config
+                .reminder
+                .remind_every
+                .map(|x| chrono::Duration::milliseconds(x as i64)),
+            max_reminders: config.reminder.max_reminders,
+            last_notification: None,
+            escalation: config.escalation,
+            escalated: 0,
+            escalation_timer: None,
+            acks: config.acks,
+        }
+    }
+
+    /// Reminder about active error, if it is due.
+    fn reminder(&self) -> Option<Message> {
+        let every = self.remind_every?;
+        let last = self.last_notification.as_ref()?;
+        if Utc::now() - last.at < every
+            || self
+                .max_reminders
+                .map(|max| last.reminders >= max)
+                .unwrap_or(false)
+        {
+            return None;
         }
+        Some(last.msg.reminder())
+    }
+
+    fn record(&mut self, res: &Result<R, E>) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(CheckResult {
+            timestamp: Utc::now(),
+            error: res.as_ref().err().map(|e| e.description()),
+        });
     }
 
     fn process_result(&mut self, res: Result<R, E>) {
+        self.record(&res);
         let msg = match (self.active_error.as_ref(), res) {
             // No active error and current observation is successful.
             (None, Ok(_)) => None,
             // No active error and current observation produced error.
             (None, Err(e)) => {
-                let msg = Some(ResourceErrorState::New(&e).create_message(&self.resource_name));
+                self.active_error_since = Utc::now();
+                let msg = Some(
+                    ResourceErrorState::New(&e)
+                        .create_message(&self.resource_name, self.active_error_since),
+                );
                 self.active_error = Some(e);
                 msg
             }
@@ -163,7 +278,8 @@ impl<R, E: ResourceError, C: Error + Send + 'static> Sentinel<R, E, C> {
                 // If error changed, report that, Otherwise do nothing.
                 if !self.sentinel_impl.compare_errors(e1, &e2) {
                     let msg = Some(
-                        ResourceErrorState::Changed(e1, &e2).create_message(&self.resource_name),
+                        ResourceErrorState::Changed(e1, &e2)
+                            .create_message(&self.resource_name, self.active_error_since),
                     );
                     self.active_error = Some(e2);
                     msg
@@ -173,15 +289,352 @@ impl<R, E: ResourceError, C: Error + Send + 'static> Sentinel<R, E, C> {
             }
             // Have active error, and observation is successful.
             (Some(e), Ok(_)) => {
-                let msg = Some(ResourceErrorState::Resolved(e).create_message(&self.resource_name));
+                let msg = Some(
+                    ResourceErrorState::Resolved(e)
+                        .create_message(&self.resource_name, self.active_error_since),
+                );
                 self.active_error = None;
                 msg
             }
         };
+        let (msg, reminders) = match msg {
+            Some(msg) => (Some(msg), 0),
+            None => (
+                self.reminder(),
+                self.last_notification
+                    .as_ref()
+                    .map_or(0, |x| x.reminders + 1),
+            ),
+        };
         if let Some(msg) = msg {
-            self.notifiers.iter().for_each(|notifier| {
-                tokio::spawn(notifier.send_message(msg.clone()));
+            let msg = msg.with_context(self.history.iter().cloned().collect(), self.info.clone());
+            self.last_notification = self.active_error.as_ref().map(|_| LastNotification {
+                msg: msg.clone(),
+                at: Utc::now(),
+                reminders,
+            });
+            // Escalated tiers get all messages up to resolution.
+            let tiers = self.escalation[..self.escalated].iter();
+            self.notifiers
+                .iter()
+                .chain(tiers.flat_map(|x| x.notifiers.iter()))
+                .for_each(|notifier| {
+                    // Errors are logged by notifiers.
+                    tokio::spawn(notifier.send_message(msg.clone()).map_err(|_| ()));
+                });
+        }
+        if !self.escalation.is_empty() {
+            self.escalate();
+        }
+    }
+
+    /// Escalate active error, when the next tier is due, without waiting for the next check.
+    fn poll_escalation(&mut self) {
+        loop {
+            let fired = match self.escalation_timer {
+                Some(ref mut timer) => match timer.poll() {
+                    Ok(Async::Ready(())) => true,
+                    Ok(Async::NotReady) => return,
+                    Err(e) => {
+                        error!("Escalation timer error of {}: {}", self.resource_name, e);
+                        false
+                    }
+                },
+                None => return,
+            };
+            self.escalation_timer = None;
+            if fired {
+                // Schedules timer of the next tier.
+                self.escalate();
+            }
+        }
+    }
+
+    /// Notify tiers, which are due, about active error, unless it is acknowledged, and
+    /// schedule the next one.
+    fn escalate(&mut self) {
+        self.escalation_timer = None;
+        let last = match (self.active_error.as_ref(), self.last_notification.as_ref()) {
+            (Some(_), Some(last)) => last,
+            _ => {
+                self.acks.clear(&self.resource_name);
+                self.escalated = 0;
+                return;
+            }
+        };
+        self.acks
+            .raise(&self.resource_name, self.active_error_since);
+        if self
+            .acks
+            .is_acked(&self.resource_name, self.active_error_since)
+        {
+            return;
+        }
+        let elapsed = Utc::now() - self.active_error_since;
+        while let Some(tier) = self.escalation.get(self.escalated) {
+            if elapsed < tier.after {
+                break;
+            }
+            self.escalated += 1;
+            info!(
+                "Escalate error of {} to tier {}",
+                self.resource_name, self.escalated
+            );
+            let msg = last
+                .msg
+                .reminder()
+                .with_context(self.history.iter().cloned().collect(), self.info.clone());
+            tier.notifiers.iter().for_each(|notifier| {
+                // Errors are logged by notifiers.
+                tokio::spawn(notifier.send_message(msg.clone()).map_err(|_| ()));
             });
         }
+        if let Some(tier) = self.escalation.get(self.escalated) {
+            let wait = (tier.after - elapsed).to_std().unwrap_or_default();
+            self.escalation_timer = Some(Delay::new(Instant::now() + wait));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, sync::Mutex};
+
+    use failure::Fail;
+    use futures::future::lazy;
+    use tokio::runtime::current_thread::Runtime;
+
+    use super::*;
+
+    struct Failure(&'static str);
+
+    impl ResourceError for Failure {
+        fn description(&self) -> String {
+            self.0.into()
+        }
+    }
+
+    /// Sentinel implementation, whose checks never complete. Tests pass results directly.
+    struct NoChecks;
+
+    impl SentinelImpl for NoChecks {
+        type ResourceOk = ();
+        type ResourceErr = Failure;
+        type SentinelErr = io::Error;
+
+        fn produce_future(&self) -> BoxedFuture<Result<(), Failure>, io::Error> {
+            Box::new(futures::future::empty())
+        }
+
+        fn compare_errors(&self, left: &Failure, right: &Failure) -> bool {
+            left.0 == right.0
+        }
+    }
+
+    /// Sender, which records states of delivered messages.
+    #[derive(Clone, Default)]
+    struct RecordSender(Arc<Mutex<Vec<String>>>);
+
+    impl RecordSender {
+        fn states(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl NotifierSender for RecordSender {
+        fn send_message(&self, msg: Message) -> BoxedFuture<(), Box<dyn Fail>> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(msg.to_json()["state"].as_str().unwrap().into());
+            Box::new(futures::future::ok(()))
+        }
+    }
+
+    type TestSentinel = Sentinel<(), Failure, io::Error>;
+
+    fn sentinel(
+        notifier: &RecordSender,
+        reminder: ReminderConfig,
+        escalation: Vec<Tier>,
+    ) -> TestSentinel {
+        Sentinel::new(
+            Box::new(NoChecks),
+            Config {
+                interval: 60_000,
+                name: "db".into(),
+                type_: "test".into(),
+                notifiers: vec![Box::new(notifier.clone())],
+                info: ResourceInfo::default(),
+                reminder,
+                escalation,
+                acks: Arc::new(Acks::default()),
+                config: serde_yaml::Value::Null,
+            },
+        )
+    }
+
+    fn tier(minutes: i64, notifier: &RecordSender) -> Tier {
+        Tier {
+            after: chrono::Duration::minutes(minutes),
+            notifiers: vec![Box::new(notifier.clone())],
+        }
+    }
+
+    /// Pretend, that active error started `minutes` earlier, like it stays active for them.
+    fn backdate_error(sentinel: &mut TestSentinel, minutes: i64) {
+        sentinel.active_error_since -= chrono::Duration::minutes(minutes);
+        // Escalation identifies error by its start, so register it again before acks.
+        sentinel
+            .acks
+            .raise(&sentinel.resource_name, sentinel.active_error_since);
+    }
+
+    /// Pretend, that last notification was sent `minutes` earlier.
+    fn backdate_notification(sentinel: &mut TestSentinel, minutes: i64) {
+        let last = sentinel.last_notification.as_mut().unwrap();
+        last.at -= chrono::Duration::minutes(minutes);
+    }
+
+    /// Run test within runtime, which is required to spawn notifications and timers.
+    fn run<F: FnOnce()>(test: F) {
+        Runtime::new()
+            .unwrap()
+            .block_on(lazy(|| {
+                test();
+                Ok::<_, ()>(())
+            }))
+            .unwrap();
+    }
+
+    #[test]
+    fn reminders_stop_after_max_and_reset_after_resolution() {
+        run(|| {
+            let notifier = RecordSender::default();
+            let reminder = ReminderConfig {
+                remind_every: Some(60_000),
+                max_reminders: Some(2),
+            };
+            let mut sentinel = sentinel(&notifier, reminder, Vec::new());
+            sentinel.process_result(Err(Failure("down")));
+            // Reminder isn't due yet.
+            sentinel.process_result(Err(Failure("down")));
+            assert_eq!(notifier.states(), vec!["new"]);
+            for _ in 0..3 {
+                backdate_notification(&mut sentinel, 2);
+                sentinel.process_result(Err(Failure("down")));
+            }
+            assert_eq!(notifier.states(), vec!["new", "reminder", "reminder"]);
+            sentinel.process_result(Ok(()));
+            sentinel.process_result(Err(Failure("down")));
+            backdate_notification(&mut sentinel, 2);
+            sentinel.process_result(Err(Failure("down")));
+            assert_eq!(notifier.states()[3..], ["resolved", "new", "reminder"]);
+        });
+    }
+
+    #[test]
+    fn changed_error_resets_reminders() {
+        run(|| {
+            let notifier = RecordSender::default();
+            let reminder = ReminderConfig {
+                remind_every: Some(60_000),
+                max_reminders: Some(1),
+            };
+            let mut sentinel = sentinel(&notifier, reminder, Vec::new());
+            sentinel.process_result(Err(Failure("down")));
+            backdate_notification(&mut sentinel, 2);
+            sentinel.process_result(Err(Failure("down")));
+            sentinel.process_result(Err(Failure("slow")));
+            backdate_notification(&mut sentinel, 2);
+            sentinel.process_result(Err(Failure("slow")));
+            backdate_notification(&mut sentinel, 2);
+            sentinel.process_result(Err(Failure("slow")));
+            assert_eq!(
+                notifier.states(),
+                vec!["new", "reminder", "changed", "reminder"]
+            );
+        });
+    }
+
+    #[test]
+    fn escalation_notifies_tiers_when_due() {
+        run(|| {
+            let (notifier, first, second) = Default::default();
+            let escalation = vec![tier(5, &first), tier(10, &second)];
+            let mut sentinel = sentinel(&notifier, ReminderConfig::default(), escalation);
+            sentinel.process_result(Err(Failure("down")));
+            assert!(first.states().is_empty());
+            // Timer waits for the first tier.
+            let deadline = sentinel.escalation_timer.as_ref().unwrap().deadline();
+            let wait = deadline - Instant::now();
+            assert!(wait > Duration::from_secs(4 * 60) && wait <= Duration::from_secs(5 * 60));
+
+            // Timer fires without waiting for the next check.
+            backdate_error(&mut sentinel, 6);
+            // Timer is already due.
+            sentinel.escalation_timer = Some(Delay::new(Instant::now() - Duration::from_secs(1)));
+            sentinel.poll_escalation();
+            assert_eq!(first.states(), vec!["reminder"]);
+            assert!(second.states().is_empty());
+            let deadline = sentinel.escalation_timer.as_ref().unwrap().deadline();
+            let wait = deadline - Instant::now();
+            assert!(wait > Duration::from_secs(3 * 60) && wait <= Duration::from_secs(4 * 60));
+
+            // Escalated tiers get all messages.
+            sentinel.process_result(Err(Failure("slow")));
+            assert_eq!(first.states(), vec!["reminder", "changed"]);
+            assert!(second.states().is_empty());
+
+            backdate_error(&mut sentinel, 5);
+            sentinel.process_result(Err(Failure("slow")));
+            assert_eq!(second.states(), vec!["reminder"]);
+            assert!(sentinel.escalation_timer.is_none());
+            assert_eq!(notifier.states(), vec!["new", "changed"]);
+        });
+    }
+
+    #[test]
+    fn escalation_resets_on_resolution() {
+        run(|| {
+            let (notifier, first) = Default::default();
+            let escalation = vec![tier(5, &first)];
+            let mut sentinel = sentinel(&notifier, ReminderConfig::default(), escalation);
+            sentinel.process_result(Err(Failure("down")));
+            backdate_error(&mut sentinel, 6);
+            sentinel.process_result(Err(Failure("down")));
+            sentinel.process_result(Ok(()));
+            assert_eq!(first.states(), vec!["reminder", "resolved"]);
+            assert_eq!(sentinel.escalated, 0);
+            assert!(sentinel.escalation_timer.is_none());
+
+            // New error starts from the first tier again.
+            sentinel.process_result(Err(Failure("down")));
+            assert_eq!(first.states().len(), 2);
+            assert!(sentinel.escalation_timer.is_some());
+        });
+    }
+
+    #[test]
+    fn ack_stops_escalation_of_active_error_only() {
+        run(|| {
+            let (notifier, first) = Default::default();
+            let escalation = vec![tier(5, &first)];
+            let mut sentinel = sentinel(&notifier, ReminderConfig::default(), escalation);
+            sentinel.process_result(Err(Failure("down")));
+            backdate_error(&mut sentinel, 6);
+            assert!(sentinel.acks.ack("db", None));
+            sentinel.process_result(Err(Failure("down")));
+            assert!(first.states().is_empty());
+            assert!(sentinel.escalation_timer.is_none());
+
+            // Acknowledgement doesn't apply to the next error.
+            sentinel.process_result(Ok(()));
+            sentinel.process_result(Err(Failure("down")));
+            backdate_error(&mut sentinel, 6);
+            sentinel.process_result(Err(Failure("down")));
+            assert_eq!(first.states(), vec!["reminder"]);
+            assert_eq!(notifier.states(), vec!["new", "resolved", "new"]);
+        });
     }
 }